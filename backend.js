@@ -0,0 +1,357 @@
+/*
+Copyright (c) 2019, cho45 <cho45@lowreal.net>
+
+All rights reserved.
+
+Redistribution and use in source and binary forms, with or without modification, are permitted provided that the following conditions are met:
+    Redistributions of source code must retain the above copyright notice, this list of conditions and the following disclaimer.
+    Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the following disclaimer in the
+    documentation and/or other materials provided with the distribution.
+    Neither the name of Great Scott Gadgets nor the names of its contributors may be used to endorse or promote products derived from this software
+    without specific prior written permission.
+
+THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO,
+THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED.
+IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+(INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION)
+HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+*/
+
+import { HackRF } from "./hackrf.js";
+import { HackRFSimulator } from "./simulator.js";
+
+// 表示のフレームレートの上限。この間に完成した掃引は捨てずに SweepAssembler で1フレームにまとめる
+const FRAME_INTERVAL_MS = 1000 / 60;
+
+// 掃引フレームのセグメント（周波数順）。表示の周波数軸に使う
+function frameSegments(plan) {
+	const assembler = plan.assembler();
+	const segments = [];
+	for (let i = 0; i < assembler.segment_count(); i++) {
+		segments.push({
+			offset: assembler.segment_offset(i),
+			binCount: assembler.segment_bin_count(i),
+			lowFreq: assembler.segment_low_freq(i) / 1e6,
+			highFreq: assembler.segment_high_freq(i) / 1e6,
+			binWidth: assembler.segment_bin_width(i) / 1e6,
+		});
+	}
+	assembler.free();
+	return segments;
+}
+
+function frameDetector(wasm, name) {
+	return name === 'mean' ? wasm.FrameDetector.Mean : wasm.FrameDetector.Max;
+}
+
+// SweepMetrics は wasm のオブジェクトなので、Comlink で渡せる普通のオブジェクトにする
+function sweepMetrics(assembler) {
+	const m = assembler.metrics();
+	const result = {
+		invalidBlocks: m.invalid_blocks,
+		outOfRangeBlocks: m.out_of_range_blocks,
+		unexpectedBlocks: m.unexpected_blocks,
+		droppedSteps: m.dropped_steps,
+		repeatedSteps: m.repeated_steps,
+		overruns: m.overruns,
+		incompleteSweeps: m.incomplete_sweeps,
+	};
+	m.free();
+	return result;
+}
+
+// worker.js が Comlink で公開する本体。wasm モジュールは init() で受け取るので、
+// テストでは Node.js 向けにビルドした hackrf-web と模擬デバイスで動かせる（hackrf-web/test-worker.mjs）
+export class Backend {
+	constructor() {
+		// 校正テーブルの選択に使う現在のゲイン設定
+		this.gains = { lnaGain: 0, vgaGain: 0, ampEnable: false };
+	}
+
+	// wasm: 初期化済みの hackrf-web モジュール
+	async init(wasm) {
+		this.wasm = wasm;
+		this.calibration = new wasm.CalibrationSet();
+		// 表示するフレームはゲイン設定を考慮して dBm に換算する
+		this.gainModel = new wasm.GainModel(0);
+	}
+
+	// 全ゲイン 0 dB のときの 0 dBFS 相当の入力電力 [dBm]（GainModel の基準オフセット）
+	async setReferenceOffset(db) {
+		this.gainModel.set_reference_offset_db(db);
+	}
+
+	// ゲイン設定（LNA / VGA / アンプ）ごとの校正テーブルを読み込む。
+	// tables は [{ lnaGain, vgaGain, ampEnable, frequencies: [Hz...], corrections: [dB...] }, ...]
+	async loadCalibration(tables) {
+		const { CalibrationSet, CalibrationTable } = this.wasm;
+		const calibration = new CalibrationSet();
+		try {
+			for (const { lnaGain, vgaGain, ampEnable, frequencies, corrections } of tables) {
+				const table = new CalibrationTable(new Float64Array(frequencies), new Float32Array(corrections));
+				calibration.insert(lnaGain, vgaGain, !!ampEnable, table);
+				table.free();
+			}
+		} catch (e) {
+			calibration.free();
+			throw e;
+		}
+		if (this.calibration) {
+			this.calibration.free();
+		}
+		this.calibration = calibration;
+		this.selectCalibration();
+		return calibration.len();
+	}
+
+	// ゲインを変えたら校正テーブルを選び直し、以前のゲインでの表示範囲の履歴を捨てる
+	gainsChanged() {
+		this.selectCalibration();
+		if (this.autoRange) {
+			this.autoRange.reset();
+		}
+	}
+
+	// 現在のゲイン設定の校正テーブルを、掃引中の SweepAssembler に設定する（完成した掃引ごとに適用される）
+	selectCalibration() {
+		if (this.assembler && this.calibration) {
+			const { lnaGain, vgaGain, ampEnable } = this.gains;
+			this.assembler.select_calibration(this.calibration, lnaGain, vgaGain, ampEnable);
+		}
+	}
+
+	async open(opts) {
+		const devices = await navigator.usb.getDevices();
+		const device = !opts ? devices[0] : devices.find( d => {
+			if (opts.vendorId) {
+				if (d.vendorId !== opts.vendorId) {
+					return false;
+				}
+			}
+			if (opts.productId) {
+				if (d.productId !== opts.productId) {
+					return false;
+				}
+			}
+			if (opts.serialNumber) {
+				if (d.serialNumber !== opts.serialNumber) {
+					return false;
+				}
+			}
+			return true;
+		});
+		if (!device) {
+			return false;
+		}
+		console.log(device);
+		this.hackrf = new HackRF();
+		await this.hackrf.open(device);
+		return true;
+	}
+
+	// 実機の代わりに模擬デバイスを開く。scene は { noiseDb, carriers: [{ frequencyHz, levelDb }, ...] }
+	async openSimulator(scene) {
+		this.hackrf = new HackRFSimulator(this.wasm, scene);
+		await this.hackrf.open();
+		return true;
+	}
+
+	async info() {
+		const { hackrf } = this;
+		const boardId = await hackrf.readBoardId();
+		const versionString = await hackrf.readVersionString();
+		const apiVersion = await hackrf.readApiVersion();
+		const { partId, serialNo } = await hackrf.readPartIdSerialNo();
+
+		let boardRev = HackRF.BOARD_REV_UNDETECTED;
+		try {
+			boardRev = await hackrf.boardRevRead();
+		} catch (e) {
+			console.log(e);
+		}
+
+		console.log(`Serial Number: ${serialNo.map( (i) => (i + 0x100000000).toString(16).slice(1) ).join('')}`)
+		console.log(`Board ID Number: ${boardId} (${HackRF.BOARD_ID_NAME.get(boardId)})`);
+		console.log(`Firmware Version: ${versionString} (API:${apiVersion[0]}.${apiVersion[1]}${apiVersion[2]})`);
+		console.log(`Part ID Number: ${partId.map( (i) => (i + 0x100000000).toString(16).slice(1) ).join(' ')}`)
+		console.log(`Board Rev: ${HackRF.BOARD_REV_NAME.get(boardRev)} (${boardRev})`)
+		return {boardId, versionString, apiVersion, partId, serialNo };
+	}
+
+	// 要求された FFT サイズで MeasurementPlan を作って start() 用に保持し、表示に使う値を返す。
+	// ranges は掃引範囲 [[開始, 終了], ...] [MHz]。複数の範囲は1回の掃引で順に受信し、
+	// 掃引フレームは範囲ごとのセグメントを周波数順に連結したものになる。
+	// FFT サイズは「RBW ≤ 要求 RBW」となる最小の 2 の冪なので、その FFT サイズでの RBW を要求すればよい。
+	// ビン数は表示幅に関係なく、start() で表示の列数にまとめる
+	async plan({ ranges, fftSize, sampleRate }) {
+		const { MeasurementPlan, WindowKind, window_enbw_bins } = this.wasm;
+		const rbwHz = window_enbw_bins(WindowKind.Blackman, fftSize) * sampleRate / fftSize;
+		const plan = MeasurementPlan.with_ranges(new Uint16Array(ranges.flat()), rbwHz, rbwHz, WindowKind.Blackman, sampleRate);
+		// start() はこの設定のとおりにデバイスと FFT を設定する
+		if (this.measurementPlan) {
+			this.measurementPlan.free();
+		}
+		this.measurementPlan = plan;
+		const result = {
+			FFT_SIZE: plan.fft_size(),
+			SAMPLE_RATE: plan.sample_rate_hz(),
+			lowFreq: plan.low_freq_mhz(),
+			highFreq: plan.high_freq_mhz(),
+			bandwidth: plan.high_freq_mhz() - plan.low_freq_mhz(),
+			freqBinCount: plan.bin_count(),
+			rbwHz: plan.rbw_hz(),
+			sweepTimeS: plan.sweep_time_s(),
+			segments: frameSegments(plan),
+		};
+		console.log('plan', result);
+		return result;
+	}
+
+	// 直前の plan() の設定（サンプルレート・ベースバンドフィルタ・窓関数・掃引計画・平均回数）で掃引を始める。
+	// callback には、前回から完成した掃引を detector ('max' / 'mean') でまとめて dBm に換算し、
+	// 周波数ビンを表示の列数 columns にまとめたフレームと、AutoRange が提案する表示範囲を渡す
+	async start({ columns, detector }, callback) {
+		const { hackrf, measurementPlan: plan } = this;
+		const { AutoRange, Decimator, Detector, FFT } = this.wasm;
+		if (!plan) {
+			throw new Error('plan() must be called before start()');
+		}
+
+		await hackrf.setSampleRateManual(plan.sample_rate_freq_hz(), plan.sample_rate_divider());
+		await hackrf.setBasebandFilterBandwidth(plan.baseband_filter_hz());
+
+		let startTime = performance.now();
+		let prevTime = startTime;
+		let readBytes = 0;
+		let bytesPerSec = 0;
+		let sweepCount = 0;
+		let sweepPerSec = 0;
+		let frameTime = startTime;
+
+		const fft = new FFT(plan.fft_size(), plan.window());
+		fft.set_smoothing_time_constant(0.0);
+		// ブロックの FFT と掃引フレームへの配置は SweepAssembler で行う。
+		// どのブロックからも書き込まれなかったビンは 0 ではなく NaN（データなし）になる。
+		// 掃引計画のステップ順を照合し、1ステップの averages ブロックを平均する（VBW）
+		const assembler = plan.assembler();
+		assembler.set_frame_detector(frameDetector(this.wasm, detector));
+		this.assembler = assembler;
+		this.selectCalibration();
+		// 直近のフレーム（約1秒）のノイズフロアとピークから表示範囲を提案する
+		const autoRange = new AutoRange(60, 0.2, 0.999, 3);
+		this.autoRange = autoRange;
+		const line    = new Float32Array(assembler.bin_count());
+		// 列内の最大値をとるので、1列より狭い信号も消えない
+		const decimator = new Decimator(assembler.bin_count(), columns, Detector.PositivePeak);
+		const display = new Float32Array(columns);
+		// libhackrf と同じく、掃引を設定してから掃引受信を始める
+		await hackrf.initSweep(
+			plan.sweep_ranges_mhz(),
+			plan.sweep_num_bytes(),
+			plan.step_width_hz(),
+			plan.sweep_offset_hz(),
+			plan.sweep_style()
+		);
+		await hackrf.startRxSweep((data) => {
+			readBytes += data.length;
+			const now = performance.now();
+			const duration = now - prevTime;
+			if (duration > 1000) {
+				bytesPerSec = readBytes / (duration / 1000);
+				prevTime = now;
+				readBytes = 0;
+			}
+
+			const completed = assembler.push_transfer(fft, data);
+			if (completed === 0) {
+				return;
+			}
+
+			sweepCount = assembler.sweep_count();
+			sweepPerSec = sweepCount / ((now - startTime) / 1000);
+			if (now - frameTime < FRAME_INTERVAL_MS) {
+				return;
+			}
+			frameTime = now;
+			const sweepsPerFrame = assembler.take_frame(line);
+			if (sweepsPerFrame === 0) {
+				return;
+			}
+			this.gainModel.to_dbm(line);
+			autoRange.update(line);
+			decimator.process(line, display);
+			callback(display, { sweepPerSec, bytesPerSec, sweepCount, sweepsPerFrame, calibrated: assembler.has_calibration(), sweep: sweepMetrics(assembler),
+				referenceLevel: autoRange.reference_level(), span: autoRange.span() });
+		});
+	}
+
+	// 掃引中の表示フレームの検波方式を変える（'max' / 'mean'）
+	async setFrameDetector(detector) {
+		if (this.assembler) {
+			this.assembler.set_frame_detector(frameDetector(this.wasm, detector));
+		}
+	}
+
+	async setSampleRateManual(freq, divider) {
+		await this.hackrf.setSampleRateManual(freq, divider);
+	}
+
+	async setBasebandFilterBandwidth(bandwidthHz) {
+		await this.hackrf.setBasebandFilterBandwidth(bandwidthHz);
+	}
+
+	async setLnaGain(value) {
+		await this.hackrf.setLnaGain(value);
+		this.gains.lnaGain = value;
+		this.gainModel.set_lna_gain(value);
+		this.gainsChanged();
+	}
+
+	async setVgaGain(value) {
+		await this.hackrf.setVgaGain(value);
+		this.gains.vgaGain = value;
+		this.gainModel.set_vga_gain(value);
+		this.gainsChanged();
+	}
+
+	async setFreq(freqHz) {
+		await this.hackrf.setFreq(freqHz);
+	}
+
+	async setAmpEnable(enable) {
+		await this.hackrf.setAmpEnable(enable);
+		this.gains.ampEnable = enable;
+		this.gainModel.set_amp_enable(enable);
+		this.gainsChanged();
+	}
+
+	async setAntennaEnable(enable) {
+		await this.hackrf.setAntennaEnable(enable);
+	}
+
+	async initSweep(ranges, numBytes, stepWidth, offset, style) {
+		await this.hackrf.initSweep(ranges, numBytes, stepWidth, offset, style);
+	}
+
+	async startRx(callback) {
+		await this.hackrf.startRx(callback);
+	}
+
+	async startRxSweep(callback) {
+		await this.hackrf.startRxSweep(callback);
+	}
+
+	async stopRx() {
+		await this.hackrf.stopRx();
+	}
+
+	async close() {
+		await this.hackrf.close();
+		await this.hackrf.exit();
+		// 模擬デバイスには USB デバイスがない
+		if (this.hackrf.device) {
+			await this.hackrf.device.forget();
+		}
+	}
+}
@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::Error;
+
+/// 直近の掃引ラインから表示の基準レベルと dB スパンを提案する。
+///
+/// 各ラインのノイズフロア（低いパーセンタイル）とピーク（高いパーセンタイル）を記録し、
+/// 履歴全体のノイズフロアの平均と最大ピークから表示範囲を決める。
+/// 提案値はヒステリシス幅を超えて変化したときだけ更新されるので、
+/// ゲイン変更時には追従しつつ、定常時には表示がちらつかない。
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct AutoRange {
+    /// ラインごとの (ノイズフロア, ピーク) [dB]
+    history: VecDeque<(f32, f32)>,
+    history_len: usize,
+    noise_percentile: f32,
+    peak_percentile: f32,
+    hysteresis_db: f32,
+    /// ノイズフロアから表示下端までの余白 [dB]
+    below_noise_db: f32,
+    /// ピークから表示上端までの余白 [dB]
+    above_peak_db: f32,
+    min_span_db: f32,
+    reference_level: f32,
+    span: f32,
+    /// 並べ替え用の作業バッファ
+    scratch: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl AutoRange {
+    /// 新しいオートレンジを作成する。
+    ///
+    /// # 引数
+    /// * `history_len` - 提案に使うラインの数
+    /// * `noise_percentile` - ノイズフロアとみなすパーセンタイル（0..1、例: 0.2）
+    /// * `peak_percentile` - ピークとみなすパーセンタイル（0..1、例: 0.999）
+    /// * `hysteresis_db` - 提案値を更新する最小の変化量 [dB]
+    ///
+    /// # エラー
+    /// * `Error::ZeroHistoryLength` - `history_len` が 0 の場合
+    /// * `Error::InvalidPercentiles` - パーセンタイルが 0..1 の範囲外、または `noise_percentile > peak_percentile` の場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(history_len: usize, noise_percentile: f32, peak_percentile: f32, hysteresis_db: f32) -> Result<AutoRange, Error> {
+        if history_len == 0 {
+            return Err(Error::ZeroHistoryLength);
+        }
+        if !((0.0..=1.0).contains(&noise_percentile) && (0.0..=1.0).contains(&peak_percentile) && noise_percentile <= peak_percentile) {
+            return Err(Error::InvalidPercentiles {
+                noise: noise_percentile,
+                peak: peak_percentile,
+            });
+        }
+
+        Ok(AutoRange {
+            history: VecDeque::with_capacity(history_len),
+            history_len,
+            noise_percentile,
+            peak_percentile,
+            hysteresis_db,
+            below_noise_db: 6.0,
+            above_peak_db: 6.0,
+            min_span_db: 20.0,
+            reference_level: 0.0,
+            span: 48.0,
+            scratch: Vec::new(),
+        })
+    }
+
+    /// ノイズフロアの下とピークの上に取る余白 [dB] を設定する。
+    pub fn set_margins(&mut self, below_noise_db: f32, above_peak_db: f32) {
+        self.below_noise_db = below_noise_db;
+        self.above_peak_db = above_peak_db;
+    }
+
+    /// 提案するスパンの最小値 [dB] を設定する。
+    pub fn set_min_span(&mut self, min_span_db: f32) {
+        self.min_span_db = min_span_db;
+    }
+
+    /// 履歴を破棄する。提案値は保持する。
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// 表示上端のレベル [dB]
+    pub fn reference_level(&self) -> f32 {
+        self.reference_level
+    }
+
+    /// 表示範囲の幅 [dB]
+    pub fn span(&self) -> f32 {
+        self.span
+    }
+
+    /// 表示下端のレベル [dB]
+    pub fn bottom_level(&self) -> f32 {
+        self.reference_level - self.span
+    }
+
+    /// 掃引ラインを履歴に加え、提案値を更新する。
+    ///
+    /// 有限でないビンは無視する。有効なビンがないラインは履歴に加えない。
+    /// 提案値が変化した場合は `true` を返す。
+    pub fn update(&mut self, line: &[f32]) -> bool {
+        self.scratch.clear();
+        self.scratch.extend(line.iter().copied().filter(|v| v.is_finite()));
+        if self.scratch.is_empty() {
+            return false;
+        }
+        self.scratch.sort_by(|a, b| a.total_cmp(b));
+        let noise = percentile(&self.scratch, self.noise_percentile);
+        let peak = percentile(&self.scratch, self.peak_percentile);
+
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back((noise, peak));
+
+        let noise = self.history.iter().map(|h| h.0).sum::<f32>() / self.history.len() as f32;
+        let peak = self.history.iter().map(|h| h.1).fold(f32::NEG_INFINITY, f32::max);
+
+        let top = peak + self.above_peak_db;
+        let bottom = noise - self.below_noise_db;
+        let span = (top - bottom).max(self.min_span_db);
+
+        if (top - self.reference_level).abs() > self.hysteresis_db || (span - self.span).abs() > self.hysteresis_db {
+            self.reference_level = top;
+            self.span = span;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 値を表示範囲で正規化する（下端で 0、上端で 1）。範囲外はクランプしない。
+    pub fn normalize(&self, value: f32) -> f32 {
+        (value - self.bottom_level()) / self.span
+    }
+
+    /// 掃引ライン全体を `normalize` して `out` に書き込む。
+    pub fn normalize_line(&self, line: &[f32], out: &mut [f32]) {
+        for (o, &v) in out.iter_mut().zip(line.iter()) {
+            *o = self.normalize(v);
+        }
+    }
+}
+
+/// 昇順に並んだスライスのパーセンタイル（最近傍順位法）
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let idx = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ノイズフロア `noise` にピーク `peak` を1本立てたライン
+    fn line_with(noise: f32, peak: f32) -> Vec<f32> {
+        let mut line = vec![noise; 1000];
+        line[500] = peak;
+        line
+    }
+
+    #[test]
+    fn test_suggests_range_from_noise_and_peak() {
+        let mut range = AutoRange::new(4, 0.2, 1.0, 1.0).unwrap();
+        range.set_margins(5.0, 5.0);
+        assert!(range.update(&line_with(-80.0, -20.0)));
+
+        assert!((range.reference_level() - (-15.0)).abs() < 1e-5);
+        assert!((range.bottom_level() - (-85.0)).abs() < 1e-5);
+        assert!((range.normalize(-85.0)).abs() < 1e-5);
+        assert!((range.normalize(-15.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut range = AutoRange::new(1, 0.2, 1.0, 3.0).unwrap();
+        assert!(range.update(&line_with(-80.0, -20.0)));
+        let reference = range.reference_level();
+
+        // ヒステリシス幅未満の変化では更新しない
+        assert!(!range.update(&line_with(-81.0, -21.0)));
+        assert_eq!(range.reference_level(), reference);
+
+        // ゲイン変更相当の大きな変化には追従する
+        assert!(range.update(&line_with(-60.0, 0.0)));
+        assert!((range.reference_level() - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_history_keeps_transient_peak() {
+        let mut range = AutoRange::new(3, 0.2, 1.0, 0.5).unwrap();
+        range.update(&line_with(-80.0, -10.0));
+        range.update(&line_with(-80.0, -40.0));
+        range.update(&line_with(-80.0, -40.0));
+        // 履歴内の最大ピークを使う
+        assert!((range.reference_level() - (-4.0)).abs() < 1e-5);
+
+        // 履歴から押し出されると下がる
+        range.update(&line_with(-80.0, -40.0));
+        assert!((range.reference_level() - (-34.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_min_span_and_invalid_bins() {
+        let mut range = AutoRange::new(1, 0.0, 1.0, 0.0).unwrap();
+        range.set_min_span(30.0);
+        let mut line = vec![-50.0f32; 10];
+        line[0] = f32::NAN;
+        line[1] = f32::NEG_INFINITY;
+        range.update(&line);
+        assert_eq!(range.span(), 30.0);
+
+        assert!(!range.update(&[f32::NAN; 4]));
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(
+            AutoRange::new(1, 0.9, 0.1, 0.0).err(),
+            Some(Error::InvalidPercentiles { noise: 0.9, peak: 0.1 })
+        );
+        assert_eq!(AutoRange::new(0, 0.1, 0.9, 0.0).err(), Some(Error::ZeroHistoryLength));
+    }
+}
@@ -0,0 +1,578 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::sweep::check_segments;
+use crate::{Error, SweepAssembler, SweepSegment};
+
+/// 標準偏差の下限の既定値 [dB]。ほとんど変化しないビンで僅かな揺らぎを異常としないため
+pub const DEFAULT_MIN_STD_DB: f32 = 0.5;
+
+/// 異常イベントの種類
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnomalyEventKind {
+    /// 最小継続時間を超えて基準を上回った
+    #[default]
+    Start = 0,
+    /// 基準を上回るビンがなくなった
+    End = 1,
+}
+
+/// 基準からの異常の開始・終了
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnomalyEvent {
+    /// 開始から終了まで同じ異常に付く番号
+    pub id: u32,
+    pub kind: AnomalyEventKind,
+    /// 異常の周波数範囲 [Hz]（最も低いビンの下端から最も高いビンの上端まで）
+    pub low_freq_hz: f64,
+    pub high_freq_hz: f64,
+    /// 最初に基準を上回った時刻 [s]
+    pub start_s: f64,
+    /// 終了した時刻 [s]。開始イベントでは NaN
+    pub end_s: f64,
+    /// 基準の平均からの最大の超過 [dB]
+    pub peak_excess_db: f32,
+}
+
+/// 継続中の異常
+#[derive(Clone, Copy, Debug)]
+struct Anomaly {
+    id: u32,
+    segment: usize,
+    low_bin: usize,
+    high_bin: usize,
+    start_s: f64,
+    peak_excess_db: f32,
+}
+
+/// ビンごとの基準（学習期間の dB 値の平均と分散）からの異常を検出する。
+///
+/// `train` / `train_sweep` で学習した後、`detect_sweep` に掃引ごとのフレームを渡す。
+/// 値が `平均 + k_sigma × max(標準偏差, min_std_db)` を `min_duration_s` 以上続けて上回ったビンを異常とし、
+/// セグメント内で連続する異常ビンを1つの異常としてまとめて開始・終了イベントを出す。
+/// 学習したサンプルが2つ未満のビンと、NaN（データなし）の値は判定しない（NaN は直前の状態を保つ）。
+///
+/// `train_sweep` で学習すると掃引のセグメント（周波数軸）も記録し、以後は同じ構成の掃引だけを受け付ける。
+/// 学習した統計と周波数軸は JSON で保存・復元できる（検出中の状態は保存しない）。
+#[wasm_bindgen]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BaselineModel {
+    k_sigma: f32,
+    min_duration_s: f64,
+    min_std_db: f32,
+    trained_sweeps: u32,
+    /// ビンごとの学習サンプル数、平均、偏差平方和（Welford 法）
+    counts: Vec<u32>,
+    means: Vec<f64>,
+    m2: Vec<f64>,
+    /// 学習した掃引のセグメント。`train` だけで学習した場合は空で、周波数軸を確かめない
+    #[serde(default)]
+    segments: Vec<SweepSegment>,
+    /// ビンが基準を上回り始めた時刻
+    #[serde(skip)]
+    exceeding_since: Vec<Option<f64>>,
+    #[serde(skip)]
+    active: Vec<Anomaly>,
+    #[serde(skip)]
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl BaselineModel {
+    /// 学習前の基準モデルを作成する。
+    ///
+    /// # 引数
+    /// * `bin_count` - フレームのビン数
+    /// * `k_sigma` - 異常とする標準偏差の倍数
+    /// * `min_duration_s` - 異常とする最小継続時間 [s]
+    ///
+    /// # エラー
+    /// * `Error::ZeroParameter` - `bin_count` が 0 の場合
+    /// * `Error::InvalidParameter` - `k_sigma` が正の有限値でない、または `min_duration_s` が 0 以上の有限値でない場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(bin_count: usize, k_sigma: f32, min_duration_s: f64) -> Result<BaselineModel, Error> {
+        if bin_count == 0 {
+            return Err(Error::ZeroParameter("Bin count"));
+        }
+        let model = BaselineModel {
+            k_sigma,
+            min_duration_s,
+            min_std_db: DEFAULT_MIN_STD_DB,
+            trained_sweeps: 0,
+            counts: vec![0; bin_count],
+            means: vec![0.0; bin_count],
+            m2: vec![0.0; bin_count],
+            segments: Vec::new(),
+            exceeding_since: vec![None; bin_count],
+            active: Vec::new(),
+            next_id: 0,
+        };
+        model.validate()?;
+        Ok(model)
+    }
+
+    /// JSON から学習済みのモデルを読み込む。
+    ///
+    /// # エラー
+    /// * `Error::Json` - JSON として解釈できない場合
+    /// * `Error::ZeroParameter` - ビン数が 0 の場合
+    /// * `Error::InputLengthMismatch` - ビンごとの統計の長さ、またはセグメントのビン数の合計が揃っていない場合
+    /// * `Error::InvalidParameter` - パラメータが範囲外、またはセグメントの `offset` が隙間なく並んでいない場合
+    pub fn from_json(json: &str) -> Result<BaselineModel, Error> {
+        let mut model: BaselineModel = serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))?;
+        if model.counts.is_empty() {
+            return Err(Error::ZeroParameter("Bin count"));
+        }
+        for len in [model.means.len(), model.m2.len()] {
+            if len != model.counts.len() {
+                return Err(Error::InputLengthMismatch { expected: model.counts.len(), actual: len });
+            }
+        }
+        if !model.segments.is_empty() {
+            check_segments(&model.segments, model.counts.len())?;
+        }
+        model.validate()?;
+        model.exceeding_since = vec![None; model.counts.len()];
+        Ok(model)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("BaselineModel is always serializable")
+    }
+
+    pub fn bin_count(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn trained_sweeps(&self) -> u32 {
+        self.trained_sweeps
+    }
+
+    pub fn k_sigma(&self) -> f32 {
+        self.k_sigma
+    }
+
+    pub fn min_duration_s(&self) -> f64 {
+        self.min_duration_s
+    }
+
+    pub fn min_std_db(&self) -> f32 {
+        self.min_std_db
+    }
+
+    /// 標準偏差の下限 [dB] を設定する。
+    ///
+    /// # エラー
+    /// * `Error::InvalidParameter` - `min_std_db` が 0 以上の有限値でない場合
+    pub fn set_min_std_db(&mut self, min_std_db: f32) -> Result<(), Error> {
+        if !(min_std_db.is_finite() && min_std_db >= 0.0) {
+            return Err(Error::InvalidParameter { name: "Minimum standard deviation", value: min_std_db as f64 });
+        }
+        self.min_std_db = min_std_db;
+        Ok(())
+    }
+
+    /// 1回の掃引のフレーム（dB）を学習する。NaN のビンは飛ばす。
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - `line.len() != bin_count` の場合
+    pub fn train(&mut self, line: &[f32]) -> Result<(), Error> {
+        if line.len() != self.counts.len() {
+            return Err(Error::InputLengthMismatch { expected: self.counts.len(), actual: line.len() });
+        }
+        for (i, &value) in line.iter().enumerate() {
+            if value.is_nan() {
+                continue;
+            }
+            self.counts[i] += 1;
+            let delta = value as f64 - self.means[i];
+            self.means[i] += delta / self.counts[i] as f64;
+            self.m2[i] += delta * (value as f64 - self.means[i]);
+        }
+        self.trained_sweeps += 1;
+        Ok(())
+    }
+
+    /// 掃引アセンブラの直前に完成したフレームを学習する。最初の呼び出しで掃引のセグメントを記録する。
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - 掃引のビン数がモデルと一致しない場合
+    /// * `Error::SegmentMismatch` - 掃引のセグメントが記録したものと一致しない場合
+    pub fn train_sweep(&mut self, sweep: &SweepAssembler) -> Result<(), Error> {
+        if !self.segments.is_empty() && self.segments != sweep.segments() {
+            return Err(Error::SegmentMismatch);
+        }
+        self.train(sweep.completed())?;
+        if self.segments.is_empty() {
+            self.segments = sweep.segments().to_vec();
+        }
+        Ok(())
+    }
+
+    /// 学習した平均 [dB] を `result` に書き込む。学習サンプルのないビンは NaN
+    ///
+    /// # エラー
+    /// * `Error::OutputLengthMismatch` - `result.len() != bin_count` の場合
+    pub fn means(&self, result: &mut [f32]) -> Result<(), Error> {
+        if result.len() != self.counts.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.counts.len(), actual: result.len() });
+        }
+        for (i, r) in result.iter_mut().enumerate() {
+            *r = if self.counts[i] == 0 { f32::NAN } else { self.means[i] as f32 };
+        }
+        Ok(())
+    }
+
+    /// 学習した標準偏差 [dB]（不偏分散から）を `result` に書き込む。学習サンプルが2つ未満のビンは NaN
+    ///
+    /// # エラー
+    /// * `Error::OutputLengthMismatch` - `result.len() != bin_count` の場合
+    pub fn std_devs(&self, result: &mut [f32]) -> Result<(), Error> {
+        if result.len() != self.counts.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.counts.len(), actual: result.len() });
+        }
+        for (i, r) in result.iter_mut().enumerate() {
+            *r = self.std_dev(i).map_or(f32::NAN, |s| s as f32);
+        }
+        Ok(())
+    }
+
+    /// 継続中の異常の数
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    /// 検出中の状態を破棄する（学習した統計は残す）。継続中の異常の終了イベントは出さない。
+    pub fn reset_detection(&mut self) {
+        self.exceeding_since.fill(None);
+        self.active.clear();
+    }
+
+    /// 掃引アセンブラの直前に完成したフレームを時刻 `timestamp_s` [s] のものとして判定する。
+    ///
+    /// # 戻り値
+    /// この掃引で発生した開始・終了イベント
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - 掃引のビン数がモデルと一致しない場合
+    /// * `Error::SegmentMismatch` - 掃引のセグメントが学習時のものと一致しない場合
+    pub fn detect_sweep(&mut self, timestamp_s: f64, sweep: &SweepAssembler) -> Result<Vec<AnomalyEvent>, Error> {
+        self.detect(timestamp_s, sweep.segments(), sweep.completed())
+    }
+}
+
+impl BaselineModel {
+    /// セグメント `segments` を持つフレーム `line` を時刻 `timestamp_s` [s] のものとして判定する。
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - `line` またはセグメントのビン数がモデルと一致しない場合
+    /// * `Error::InvalidParameter` - セグメントの `offset` がフレームを先頭から隙間なく並んでいない場合
+    /// * `Error::SegmentMismatch` - 周波数軸を学習していて、`segments` がそれと一致しない場合
+    pub fn detect(&mut self, timestamp_s: f64, segments: &[SweepSegment], line: &[f32]) -> Result<Vec<AnomalyEvent>, Error> {
+        let bin_count = self.counts.len();
+        if line.len() != bin_count {
+            return Err(Error::InputLengthMismatch { expected: bin_count, actual: line.len() });
+        }
+        let segment_bins: usize = segments.iter().map(|s| s.bin_count).sum();
+        if segment_bins != bin_count {
+            return Err(Error::InputLengthMismatch { expected: bin_count, actual: segment_bins });
+        }
+        check_segments(segments, bin_count)?;
+        if !self.segments.is_empty() && self.segments != segments {
+            return Err(Error::SegmentMismatch);
+        }
+
+        for (i, &value) in line.iter().enumerate() {
+            if value.is_nan() {
+                continue;
+            }
+            let exceeding = self.std_dev(i).is_some_and(|std| {
+                value as f64 > self.means[i] + self.k_sigma as f64 * std.max(self.min_std_db as f64)
+            });
+            self.exceeding_since[i] = if exceeding { self.exceeding_since[i].or(Some(timestamp_s)) } else { None };
+        }
+
+        let mut active = Vec::with_capacity(self.active.len());
+        let mut matched = vec![false; self.active.len()];
+        let mut events = Vec::new();
+        for (segment_index, segment) in segments.iter().enumerate() {
+            let mut bin = segment.offset;
+            let end = segment.offset + segment.bin_count;
+            while bin < end {
+                if !self.confirmed(bin, timestamp_s) {
+                    bin += 1;
+                    continue;
+                }
+                let low_bin = bin;
+                let mut start_s = f64::INFINITY;
+                let mut peak_excess_db = f32::NEG_INFINITY;
+                while bin < end && self.confirmed(bin, timestamp_s) {
+                    start_s = start_s.min(self.exceeding_since[bin].unwrap_or(timestamp_s));
+                    if !line[bin].is_nan() {
+                        peak_excess_db = peak_excess_db.max((line[bin] as f64 - self.means[bin]) as f32);
+                    }
+                    bin += 1;
+                }
+                let high_bin = bin - 1;
+
+                // 前の掃引の異常と重なれば同じ異常として続ける
+                let previous = (0..self.active.len()).find(|&i| {
+                    let a = &self.active[i];
+                    !matched[i] && a.segment == segment_index && a.low_bin <= high_bin && low_bin <= a.high_bin
+                });
+                let anomaly = match previous {
+                    Some(i) => {
+                        matched[i] = true;
+                        let previous = self.active[i];
+                        Anomaly { low_bin, high_bin, peak_excess_db: previous.peak_excess_db.max(peak_excess_db), ..previous }
+                    }
+                    None => {
+                        let anomaly =
+                            Anomaly { id: self.next_id, segment: segment_index, low_bin, high_bin, start_s, peak_excess_db };
+                        self.next_id = self.next_id.wrapping_add(1);
+                        events.push(event(&anomaly, segment, AnomalyEventKind::Start, f64::NAN));
+                        anomaly
+                    }
+                };
+                active.push(anomaly);
+            }
+        }
+
+        for (anomaly, _) in self.active.iter().zip(&matched).filter(|(_, &m)| !m) {
+            // 同じ構成の掃引なら、前の異常のセグメントは今回もある
+            if let Some(segment) = segments.get(anomaly.segment) {
+                events.push(event(anomaly, segment, AnomalyEventKind::End, timestamp_s));
+            }
+        }
+        self.active = active;
+        Ok(events)
+    }
+
+    fn std_dev(&self, bin: usize) -> Option<f64> {
+        (self.counts[bin] >= 2).then(|| (self.m2[bin] / (self.counts[bin] - 1) as f64).sqrt())
+    }
+
+    /// ビンが最小継続時間以上続けて基準を上回っているか
+    fn confirmed(&self, bin: usize, timestamp_s: f64) -> bool {
+        self.exceeding_since[bin].is_some_and(|since| timestamp_s - since >= self.min_duration_s)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if !(self.k_sigma.is_finite() && self.k_sigma > 0.0) {
+            return Err(Error::InvalidParameter { name: "Sigma multiplier", value: self.k_sigma as f64 });
+        }
+        if !(self.min_duration_s.is_finite() && self.min_duration_s >= 0.0) {
+            return Err(Error::InvalidParameter { name: "Minimum duration", value: self.min_duration_s });
+        }
+        if !(self.min_std_db.is_finite() && self.min_std_db >= 0.0) {
+            return Err(Error::InvalidParameter { name: "Minimum standard deviation", value: self.min_std_db as f64 });
+        }
+        Ok(())
+    }
+}
+
+fn event(anomaly: &Anomaly, segment: &SweepSegment, kind: AnomalyEventKind, end_s: f64) -> AnomalyEvent {
+    AnomalyEvent {
+        id: anomaly.id,
+        kind,
+        low_freq_hz: segment.bin_frequency(anomaly.low_bin - segment.offset),
+        high_freq_hz: segment.bin_frequency(anomaly.high_bin - segment.offset + 1),
+        start_s: anomaly.start_s,
+        end_s,
+        peak_excess_db: anomaly.peak_excess_db,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100..110 MHz と 200..210 MHz、各 10 ビン（1 MHz 間隔）
+    const SEGMENTS: [SweepSegment; 2] = [
+        SweepSegment { low_freq_hz: 100e6, high_freq_hz: 110e6, bin_width_hz: 1e6, offset: 0, bin_count: 10 },
+        SweepSegment { low_freq_hz: 200e6, high_freq_hz: 210e6, bin_width_hz: 1e6, offset: 10, bin_count: 10 },
+    ];
+
+    /// -90 dB と -88 dB を交互に学習したモデル（平均 -89 dB、標準偏差 約 1.05 dB）
+    fn trained(k_sigma: f32, min_duration_s: f64) -> BaselineModel {
+        let mut model = BaselineModel::new(20, k_sigma, min_duration_s).unwrap();
+        for i in 0..10 {
+            model.train(&[if i % 2 == 0 { -90.0 } else { -88.0 }; 20]).unwrap();
+        }
+        model
+    }
+
+    fn line(high_bins: &[usize], level: f32) -> Vec<f32> {
+        let mut line = vec![-89.0; 20];
+        for &bin in high_bins {
+            line[bin] = level;
+        }
+        line
+    }
+
+    #[test]
+    fn test_training_statistics() {
+        let mut model = BaselineModel::new(3, 3.0, 0.0).unwrap();
+        model.train(&[-80.0, -70.0, f32::NAN]).unwrap();
+        model.train(&[-84.0, -70.0, f32::NAN]).unwrap();
+        model.train(&[-82.0, -70.0, -60.0]).unwrap();
+        assert_eq!(model.trained_sweeps(), 3);
+
+        let mut means = [0.0; 3];
+        model.means(&mut means).unwrap();
+        assert_eq!(means, [-82.0, -70.0, -60.0]);
+        let mut std = [0.0; 3];
+        model.std_devs(&mut std).unwrap();
+        assert!((std[0] - 2.0).abs() < 1e-6);
+        assert_eq!(std[1], 0.0);
+        // 1サンプルでは分散が決まらない
+        assert!(std[2].is_nan());
+    }
+
+    #[test]
+    fn test_minimum_duration() {
+        let mut model = trained(3.0, 1.0);
+        // 閾値は -89 + 3 × 1.05 ≒ -85.8 dB
+        assert!(model.detect(0.0, &SEGMENTS, &line(&[3, 4, 5], -60.0)).unwrap().is_empty());
+        assert!(model.detect(0.5, &SEGMENTS, &line(&[3, 4, 5], -60.0)).unwrap().is_empty());
+
+        let events = model.detect(1.0, &SEGMENTS, &line(&[3, 4, 5, 6], -60.0)).unwrap();
+        // ビン 6 は超えたばかりなので含まない
+        assert_eq!(events.len(), 1);
+        let start = events[0];
+        assert_eq!((start.id, start.kind), (0, AnomalyEventKind::Start));
+        assert_eq!((start.low_freq_hz, start.high_freq_hz), (103e6, 106e6));
+        assert_eq!(start.start_s, 0.0);
+        assert_eq!(start.peak_excess_db, 29.0);
+        assert!(events[0].end_s.is_nan());
+        assert_eq!(model.active_count(), 1);
+
+        // 広がっても同じ異常のまま
+        assert!(model.detect(2.0, &SEGMENTS, &line(&[3, 4, 5, 6], -50.0)).unwrap().is_empty());
+        let events = model.detect(3.0, &SEGMENTS, &line(&[], 0.0)).unwrap();
+        assert_eq!(events.len(), 1);
+        let end = events[0];
+        assert_eq!((end.id, end.kind), (0, AnomalyEventKind::End));
+        assert_eq!((end.low_freq_hz, end.high_freq_hz), (103e6, 107e6));
+        assert_eq!((end.start_s, end.end_s), (0.0, 3.0));
+        assert_eq!(end.peak_excess_db, 39.0);
+        assert_eq!(model.active_count(), 0);
+
+        // 途切れたら継続時間は数え直す
+        model.detect(4.0, &SEGMENTS, &line(&[3], -60.0)).unwrap();
+        model.detect(4.5, &SEGMENTS, &line(&[], 0.0)).unwrap();
+        assert!(model.detect(5.0, &SEGMENTS, &line(&[3], -60.0)).unwrap().is_empty());
+        let events = model.detect(6.0, &SEGMENTS, &line(&[3], -60.0)).unwrap();
+        assert_eq!((events[0].id, events[0].start_s), (1, 5.0));
+    }
+
+    #[test]
+    fn test_segments_and_no_data() {
+        let mut model = trained(3.0, 0.0);
+        // 小さい超過は標準偏差の k 倍以内
+        assert!(model.detect(0.0, &SEGMENTS, &line(&[0], -87.0)).unwrap().is_empty());
+
+        // ビン 9 と 10 は隣り合っているが別のセグメントなので別の異常
+        let events = model.detect(1.0, &SEGMENTS, &line(&[9, 10], -60.0)).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].low_freq_hz, events[0].high_freq_hz), (109e6, 110e6));
+        assert_eq!((events[1].low_freq_hz, events[1].high_freq_hz), (200e6, 201e6));
+        assert_ne!(events[0].id, events[1].id);
+
+        // NaN のビンは状態を保つ
+        let mut no_data = line(&[9], -60.0);
+        no_data[10] = f32::NAN;
+        assert!(model.detect(2.0, &SEGMENTS, &no_data).unwrap().is_empty());
+        let events = model.detect(3.0, &SEGMENTS, &line(&[9], -60.0)).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].kind, events[0].low_freq_hz), (AnomalyEventKind::End, 200e6));
+
+        // 学習していないビンは判定しない
+        let mut untrained = BaselineModel::new(20, 3.0, 0.0).unwrap();
+        assert!(untrained.detect(0.0, &SEGMENTS, &line(&[0], 0.0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut model = trained(4.0, 2.5);
+        model.set_min_std_db(1.5).unwrap();
+        model.detect(0.0, &SEGMENTS, &line(&[3], -60.0)).unwrap();
+
+        let json = model.to_json();
+        let mut restored = BaselineModel::from_json(&json).unwrap();
+        assert_eq!(restored.to_json(), json);
+        assert_eq!((restored.k_sigma(), restored.min_duration_s(), restored.min_std_db()), (4.0, 2.5, 1.5));
+        assert_eq!(restored.trained_sweeps(), 10);
+        // 検出中の状態は保存しない
+        assert!(restored.detect(2.5, &SEGMENTS, &line(&[3], -60.0)).unwrap().is_empty());
+        assert_eq!(model.detect(2.5, &SEGMENTS, &line(&[3], -60.0)).unwrap().len(), 1);
+
+        let mut means = [0.0; 20];
+        restored.means(&mut means).unwrap();
+        assert!(means.iter().all(|&m| m == -89.0));
+    }
+
+    #[test]
+    fn test_trained_frequency_axis() {
+        // low_freq_hz から 40 MHz、16 ビンの掃引を1回完成させる
+        let sweep = |low_freq_hz: f64| {
+            let mut sweep = SweepAssembler::new(low_freq_hz, low_freq_hz + 40e6, 16).unwrap();
+            let base = low_freq_hz as u64;
+            sweep.place(base, &[-90.0; 8]);
+            sweep.place(base + 20_000_000, &[-90.0; 8]);
+            sweep.place(base, &[-90.0; 8]);
+            sweep
+        };
+        let trained_sweep = sweep(100e6);
+        let other_sweep = sweep(200e6);
+
+        let mut model = BaselineModel::new(16, 3.0, 0.0).unwrap();
+        model.train_sweep(&trained_sweep).unwrap();
+        model.train_sweep(&trained_sweep).unwrap();
+        // ビン数が同じでも周波数軸が違う掃引は受け付けない
+        assert_eq!(model.train_sweep(&other_sweep), Err(Error::SegmentMismatch));
+        assert_eq!(model.trained_sweeps(), 2);
+
+        // 周波数軸は JSON にも残る
+        let mut restored = BaselineModel::from_json(&model.to_json()).unwrap();
+        assert!(restored.detect_sweep(0.0, &trained_sweep).unwrap().is_empty());
+        assert_eq!(restored.detect_sweep(1.0, &other_sweep).err(), Some(Error::SegmentMismatch));
+        assert_eq!(restored.detect(1.0, other_sweep.segments(), &[-90.0; 16]).err(), Some(Error::SegmentMismatch));
+
+        // 周波数軸のビン数がモデルと揃っていない JSON は読み込まない
+        let json = model.to_json().replace(r#""bin_count":16"#, r#""bin_count":15"#);
+        assert_eq!(BaselineModel::from_json(&json).err(), Some(Error::InputLengthMismatch { expected: 15, actual: 16 }));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(BaselineModel::new(0, 3.0, 0.0).err(), Some(Error::ZeroParameter("Bin count")));
+        assert_eq!(
+            BaselineModel::new(1, 0.0, 0.0).err(),
+            Some(Error::InvalidParameter { name: "Sigma multiplier", value: 0.0 })
+        );
+        assert_eq!(
+            BaselineModel::new(1, 3.0, -1.0).err(),
+            Some(Error::InvalidParameter { name: "Minimum duration", value: -1.0 })
+        );
+
+        let mut model = BaselineModel::new(20, 3.0, 0.0).unwrap();
+        assert!(model.set_min_std_db(f32::NAN).is_err());
+        assert_eq!(model.train(&[0.0; 3]), Err(Error::InputLengthMismatch { expected: 20, actual: 3 }));
+        assert_eq!(model.detect(0.0, &SEGMENTS[..1], &[0.0; 20]), Err(Error::InputLengthMismatch { expected: 20, actual: 10 }));
+        // ビン数の合計が合っていても、offset がずれていればパニックせずエラーにする
+        let mut shifted = SEGMENTS;
+        shifted[1].offset = 15;
+        assert_eq!(
+            model.detect(0.0, &shifted, &[0.0; 20]),
+            Err(Error::InvalidParameter { name: "Segment offset", value: 15.0 })
+        );
+        assert_eq!(model.means(&mut [0.0; 3]), Err(Error::OutputLengthMismatch { expected: 20, actual: 3 }));
+
+        assert!(matches!(BaselineModel::from_json("{}"), Err(Error::Json(_))));
+        let json = model.to_json().replace(r#""m2":[0.0,"#, r#""m2":["#);
+        assert_eq!(BaselineModel::from_json(&json).err(), Some(Error::InputLengthMismatch { expected: 20, actual: 19 }));
+    }
+}
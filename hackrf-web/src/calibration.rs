@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::sweep::{check_segments, SweepSegment};
+use crate::Error;
+
+/// 周波数特性（平坦度）の校正テーブル。
+///
+/// 周波数 [Hz] → 補正量 [dB] の点列を保持し、点の間は線形補間する。
+/// 範囲外の周波数には端点の補正量をそのまま使う。
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationTable {
+    /// 昇順に並んだ周波数 [Hz]
+    freqs_hz: Vec<f64>,
+    /// `freqs_hz` に対応する補正量 [dB]
+    corrections_db: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl CalibrationTable {
+    /// 周波数と補正量の組から校正テーブルを作成する。
+    ///
+    /// 点の順序は任意で、内部で周波数順に並べ替える。
+    ///
+    /// # エラー
+    /// * `Error::EmptyCalibrationTable` - 点が1つもない場合
+    /// * `Error::CalibrationLengthMismatch` - `freqs_hz.len() != corrections_db.len()` の場合
+    /// * `Error::NonFiniteValue` - 有限でない値が含まれる場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(freqs_hz: &[f64], corrections_db: &[f32]) -> Result<CalibrationTable, Error> {
+        if freqs_hz.is_empty() {
+            return Err(Error::EmptyCalibrationTable);
+        }
+        if freqs_hz.len() != corrections_db.len() {
+            return Err(Error::CalibrationLengthMismatch {
+                frequencies: freqs_hz.len(),
+                corrections: corrections_db.len(),
+            });
+        }
+        if !(freqs_hz.iter().all(|f| f.is_finite()) && corrections_db.iter().all(|c| c.is_finite())) {
+            return Err(Error::NonFiniteValue);
+        }
+
+        let mut points: Vec<(f64, f32)> = freqs_hz.iter().copied().zip(corrections_db.iter().copied()).collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(CalibrationTable {
+            freqs_hz: points.iter().map(|p| p.0).collect(),
+            corrections_db: points.iter().map(|p| p.1).collect(),
+        })
+    }
+
+    /// 既知の平坦な雑音源を掃引した1ラインから校正テーブルを作成する。
+    ///
+    /// ラインを `segments` 個の区間に分け、各区間の中央値（スプリアスに強い）を
+    /// その区間中心での応答とみなす。補正量は `target_db - 応答` となる。
+    /// `target_db` を省略した場合は全区間の中央値を目標とし、相対的な平坦度のみを補正する。
+    ///
+    /// 有限でないビン（データなし）は無視する。有効なビンを含まない区間は点を生成しない。
+    ///
+    /// # 引数
+    /// * `line` - 掃引ライン（dB）
+    /// * `start_hz` - `line[0]` の周波数
+    /// * `bin_hz` - ビン間隔
+    /// * `segments` - 区間数（= 生成される点の最大数）
+    /// * `target_db` - 補正後に揃えるレベル
+    ///
+    /// # エラー
+    /// * `Error::ZeroSegmentCount` - `segments` が 0 の場合
+    /// * `Error::NoValidBins` - 有効なビンが1つもない場合
+    pub fn from_flat_reference(
+        line: &[f32],
+        start_hz: f64,
+        bin_hz: f64,
+        segments: usize,
+        target_db: Option<f32>,
+    ) -> Result<CalibrationTable, Error> {
+        if segments == 0 {
+            return Err(Error::ZeroSegmentCount);
+        }
+
+        let segments = segments.min(line.len().max(1));
+        let mut freqs = Vec::with_capacity(segments);
+        let mut levels = Vec::with_capacity(segments);
+        for s in 0..segments {
+            let from = s * line.len() / segments;
+            let to = (s + 1) * line.len() / segments;
+            let mut values: Vec<f32> = line[from..to].iter().copied().filter(|v| v.is_finite()).collect();
+            if values.is_empty() {
+                continue;
+            }
+            freqs.push(start_hz + bin_hz * (from + to - 1) as f64 / 2.0);
+            levels.push(median(&mut values));
+        }
+        if levels.is_empty() {
+            return Err(Error::NoValidBins);
+        }
+
+        let target = target_db.unwrap_or_else(|| median(&mut levels.clone()));
+        let corrections: Vec<f32> = levels.iter().map(|&l| target - l).collect();
+        CalibrationTable::new(&freqs, &corrections)
+    }
+
+    /// 点の数
+    pub fn len(&self) -> usize {
+        self.freqs_hz.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.freqs_hz.is_empty()
+    }
+
+    /// 周波数順に並んだ点の周波数 [Hz]
+    pub fn frequencies(&self) -> Vec<f64> {
+        self.freqs_hz.clone()
+    }
+
+    /// `frequencies()` に対応する補正量 [dB]
+    pub fn corrections(&self) -> Vec<f32> {
+        self.corrections_db.clone()
+    }
+
+    /// 指定周波数での補正量 [dB] を線形補間で求める。
+    pub fn correction_at(&self, freq_hz: f64) -> f32 {
+        let freqs = &self.freqs_hz;
+        let last = freqs.len() - 1;
+        if freq_hz <= freqs[0] {
+            return self.corrections_db[0];
+        }
+        if freq_hz >= freqs[last] {
+            return self.corrections_db[last];
+        }
+
+        // freqs[i - 1] < freq_hz <= freqs[i] となる i
+        let i = freqs.partition_point(|&f| f < freq_hz);
+        let (f0, f1) = (freqs[i - 1], freqs[i]);
+        let (c0, c1) = (self.corrections_db[i - 1], self.corrections_db[i]);
+        let t = ((freq_hz - f0) / (f1 - f0)) as f32;
+        c0 + (c1 - c0) * t
+    }
+
+    /// 掃引ラインに補正量を加算する。
+    ///
+    /// `line[i]` の周波数は `start_hz + i * bin_hz` とみなす。
+    pub fn apply(&self, line: &mut [f32], start_hz: f64, bin_hz: f64) {
+        for (i, v) in line.iter_mut().enumerate() {
+            *v += self.correction_at(start_hz + bin_hz * i as f64);
+        }
+    }
+}
+
+impl CalibrationTable {
+    /// 掃引フレームの各セグメントに、そのセグメントの周波数軸で補正量を加算する。
+    ///
+    /// `SweepAssembler::segments()` のように、複数範囲のフレームは範囲ごとに周波数軸が異なる。
+    ///
+    /// # エラー
+    /// * `check_segments` と同じ（セグメントがフレームを隙間なく覆っていない場合）
+    pub fn apply_segments(&self, frame: &mut [f32], segments: &[SweepSegment]) -> Result<(), Error> {
+        check_segments(segments, frame.len())?;
+        for segment in segments {
+            let line = &mut frame[segment.offset..segment.offset + segment.bin_count];
+            self.apply(line, segment.low_freq_hz, segment.bin_width_hz);
+        }
+        Ok(())
+    }
+}
+
+/// ゲイン設定（LNA / VGA / アンプ）ごとの校正テーブルの集合。
+///
+/// 同じゲイン設定のテーブルがない場合は、アンプの状態が同じテーブルのうち
+/// LNA と VGA のゲイン差の合計が最も小さいものを使う。
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct CalibrationSet {
+    tables: HashMap<(u32, u32, bool), CalibrationTable>,
+}
+
+#[wasm_bindgen]
+impl CalibrationSet {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ゲイン設定に対応するテーブルを登録する。既存のテーブルは置き換える。
+    pub fn insert(&mut self, lna_gain: u32, vga_gain: u32, amp_enable: bool, table: &CalibrationTable) {
+        self.tables.insert((lna_gain, vga_gain, amp_enable), table.clone());
+    }
+
+    /// 登録されたテーブルの数
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// ゲイン設定に対応するテーブルで掃引ラインを補正する。
+    ///
+    /// 使えるテーブルがなく補正しなかった場合は `false` を返す。
+    pub fn apply(&self, lna_gain: u32, vga_gain: u32, amp_enable: bool, line: &mut [f32], start_hz: f64, bin_hz: f64) -> bool {
+        match self.lookup(lna_gain, vga_gain, amp_enable) {
+            Some(table) => {
+                table.apply(line, start_hz, bin_hz);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl CalibrationSet {
+    /// ゲイン設定に使うテーブルを選ぶ。
+    pub fn lookup(&self, lna_gain: u32, vga_gain: u32, amp_enable: bool) -> Option<&CalibrationTable> {
+        if let Some(table) = self.tables.get(&(lna_gain, vga_gain, amp_enable)) {
+            return Some(table);
+        }
+        self.tables
+            .iter()
+            .filter(|((_, _, amp), _)| *amp == amp_enable)
+            .min_by_key(|((lna, vga, _), _)| (lna.abs_diff(lna_gain) + vga.abs_diff(vga_gain), *lna, *vga))
+            .map(|(_, table)| table)
+    }
+
+    /// ゲイン設定に対応するテーブルで掃引フレームの各セグメントを補正する。
+    ///
+    /// 使えるテーブルがなく補正しなかった場合は `false` を返す。
+    ///
+    /// # エラー
+    /// * `CalibrationTable::apply_segments` と同じ
+    pub fn apply_segments(&self, lna_gain: u32, vga_gain: u32, amp_enable: bool, frame: &mut [f32], segments: &[SweepSegment]) -> Result<bool, Error> {
+        match self.lookup(lna_gain, vga_gain, amp_enable) {
+            Some(table) => table.apply_segments(frame, segments).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+/// 有限値のみを含むスライスの中央値。スライスは並べ替えられる。
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correction_interpolation() {
+        let table = CalibrationTable::new(&[100e6, 200e6, 400e6], &[1.0, 3.0, -1.0]).unwrap();
+
+        assert_eq!(table.correction_at(100e6), 1.0);
+        assert!((table.correction_at(150e6) - 2.0).abs() < 1e-6);
+        assert!((table.correction_at(300e6) - 1.0).abs() < 1e-6);
+        // 範囲外は端点の値を保持
+        assert_eq!(table.correction_at(10e6), 1.0);
+        assert_eq!(table.correction_at(6000e6), -1.0);
+    }
+
+    #[test]
+    fn test_points_are_sorted() {
+        let table = CalibrationTable::new(&[300e6, 100e6, 200e6], &[3.0, 1.0, 2.0]).unwrap();
+        assert_eq!(table.frequencies(), vec![100e6, 200e6, 300e6]);
+        assert_eq!(table.corrections(), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_apply_to_line() {
+        let table = CalibrationTable::new(&[0.0, 10.0], &[0.0, 10.0]).unwrap();
+        let mut line = vec![-50.0f32; 11];
+        table.apply(&mut line, 0.0, 1.0);
+        for (i, &v) in line.iter().enumerate() {
+            assert!((v - (-50.0 + i as f32)).abs() < 1e-5, "line[{}] = {}", i, v);
+        }
+    }
+
+    #[test]
+    fn test_apply_segments() {
+        // 2つの範囲（100 MHz から 1 MHz 間隔、1000 MHz から 2 MHz 間隔）
+        let segments = [
+            SweepSegment { low_freq_hz: 100e6, high_freq_hz: 103e6, bin_width_hz: 1e6, offset: 0, bin_count: 3 },
+            SweepSegment { low_freq_hz: 1000e6, high_freq_hz: 1004e6, bin_width_hz: 2e6, offset: 3, bin_count: 2 },
+        ];
+        let table = CalibrationTable::new(&[100e6, 1100e6], &[0.0, 10.0]).unwrap();
+        let mut frame = vec![0.0f32, 0.0, 0.0, 0.0, f32::NAN];
+        table.apply_segments(&mut frame, &segments).unwrap();
+        let expected = [0.0, 0.01, 0.02, 9.0];
+        for (v, e) in frame.iter().zip(expected) {
+            assert!((v - e).abs() < 1e-5, "{:?}", frame);
+        }
+        // データなしのビンはそのまま
+        assert!(frame[4].is_nan());
+
+        assert_eq!(
+            table.apply_segments(&mut [0.0; 4], &segments),
+            Err(Error::InputLengthMismatch { expected: 5, actual: 4 })
+        );
+
+        let mut set = CalibrationSet::new();
+        let mut frame = vec![0.0f32; 5];
+        assert_eq!(set.apply_segments(16, 20, false, &mut frame, &segments), Ok(false));
+        set.insert(16, 20, false, &table);
+        assert_eq!(set.apply_segments(8, 20, false, &mut frame, &segments), Ok(true));
+        assert!((frame[3] - 9.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_from_flat_reference_flattens_response() {
+        // 周波数に対して 0.01 dB/bin で傾いた応答 + スプリアス1本
+        let n = 1000;
+        let mut line: Vec<f32> = (0..n).map(|i| -60.0 + 0.01 * i as f32).collect();
+        line[123] = 0.0;
+        line[500] = f32::NAN;
+
+        let table = CalibrationTable::from_flat_reference(&line, 1e9, 1e4, 20, Some(-60.0)).unwrap();
+        assert_eq!(table.len(), 20);
+
+        let mut corrected: Vec<f32> = (0..n).map(|i| -60.0 + 0.01 * i as f32).collect();
+        table.apply(&mut corrected, 1e9, 1e4);
+        // 端の半区間は外挿しないため、内側のビンのみ検証する
+        for (i, &v) in corrected.iter().enumerate().skip(25).take(n - 50) {
+            assert!((v + 60.0).abs() < 0.05, "corrected[{}] = {}", i, v);
+        }
+    }
+
+    #[test]
+    fn test_from_flat_reference_relative() {
+        let line = vec![-40.0f32; 100];
+        let table = CalibrationTable::from_flat_reference(&line, 0.0, 1.0, 4, None).unwrap();
+        assert!(table.corrections().iter().all(|&c| c == 0.0));
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        assert_eq!(
+            CalibrationTable::new(&[1.0, 2.0], &[0.0]).err(),
+            Some(Error::CalibrationLengthMismatch { frequencies: 2, corrections: 1 })
+        );
+        assert_eq!(CalibrationTable::new(&[], &[]).err(), Some(Error::EmptyCalibrationTable));
+        assert_eq!(CalibrationTable::new(&[f64::NAN], &[0.0]).err(), Some(Error::NonFiniteValue));
+        assert_eq!(
+            CalibrationTable::from_flat_reference(&[1.0], 0.0, 1.0, 0, None).err(),
+            Some(Error::ZeroSegmentCount)
+        );
+        assert_eq!(
+            CalibrationTable::from_flat_reference(&[f32::NAN; 4], 0.0, 1.0, 2, None).err(),
+            Some(Error::NoValidBins)
+        );
+    }
+
+    #[test]
+    fn test_set_lookup_per_gain() {
+        let mut set = CalibrationSet::new();
+        set.insert(16, 20, false, &CalibrationTable::new(&[0.0], &[1.0]).unwrap());
+        set.insert(32, 20, false, &CalibrationTable::new(&[0.0], &[2.0]).unwrap());
+        set.insert(16, 20, true, &CalibrationTable::new(&[0.0], &[3.0]).unwrap());
+
+        let mut line = vec![0.0f32; 1];
+        assert!(set.apply(16, 20, true, &mut line, 0.0, 1.0));
+        assert_eq!(line[0], 3.0);
+
+        // 完全一致がない場合はアンプ状態が同じ最寄りの設定
+        assert_eq!(set.lookup(24, 22, false).unwrap().correction_at(0.0), 1.0);
+        assert_eq!(set.lookup(40, 20, false).unwrap().correction_at(0.0), 2.0);
+
+        let empty = CalibrationSet::new();
+        let mut line = vec![0.0f32; 1];
+        assert!(!empty.apply(16, 20, false, &mut line, 0.0, 1.0));
+        assert_eq!(line[0], 0.0);
+    }
+}
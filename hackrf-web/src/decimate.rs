@@ -0,0 +1,197 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Error;
+
+/// 表示の1列に複数のビンをまとめるときの検波方式（スペクトラムアナライザと同じ）
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Detector {
+    /// 最大値
+    #[default]
+    PositivePeak = 0,
+    /// 最小値
+    NegativePeak = 1,
+    /// 列の中央のビン
+    Sample = 2,
+    /// dB 値の平均（対数平均）
+    Average = 3,
+    /// 電力の平均（RMS）
+    Rms = 4,
+}
+
+/// 任意の数の周波数ビンを、表示の列数に合わせてまとめる。
+///
+/// 列 `c` には `c * bin_count / columns` から `(c + 1) * bin_count / columns` 未満のビンを割り当てる。
+/// 列の数がビン数以上の場合は、列の中央に最も近いビンを使う。
+/// NaN（データなし）のビンは無視し、列の全てのビンが NaN なら結果も NaN になる。
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Decimator {
+    detector: Detector,
+    bin_count: usize,
+    /// 列ごとのビンの範囲 (開始, 終了)
+    ranges: Vec<(usize, usize)>,
+}
+
+#[wasm_bindgen]
+impl Decimator {
+    /// 新しいデシメータを作成する。
+    ///
+    /// # 引数
+    /// * `bin_count` - 入力のビン数
+    /// * `columns` - 出力の列数（表示の幅）
+    /// * `detector` - 検波方式
+    ///
+    /// # エラー
+    /// * `Error::ZeroParameter` - `bin_count` または `columns` が 0 の場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(bin_count: usize, columns: usize, detector: Detector) -> Result<Decimator, Error> {
+        if bin_count == 0 {
+            return Err(Error::ZeroParameter("Bin count"));
+        }
+        if columns == 0 {
+            return Err(Error::ZeroParameter("Column count"));
+        }
+
+        let ranges = (0..columns)
+            .map(|c| {
+                if columns < bin_count {
+                    (c * bin_count / columns, (c + 1) * bin_count / columns)
+                } else {
+                    let center = (2 * c + 1) * bin_count / (2 * columns);
+                    (center, center + 1)
+                }
+            })
+            .collect();
+        Ok(Decimator { detector, bin_count, ranges })
+    }
+
+    pub fn bin_count(&self) -> usize {
+        self.bin_count
+    }
+
+    pub fn columns(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn detector(&self) -> Detector {
+        self.detector
+    }
+
+    pub fn set_detector(&mut self, detector: Detector) {
+        self.detector = detector;
+    }
+
+    /// `input`（dB）を列ごとにまとめて `output` に書き込む。
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - `input.len() != bin_count` の場合
+    /// * `Error::OutputLengthMismatch` - `output.len() != columns` の場合
+    pub fn process(&self, input: &[f32], output: &mut [f32]) -> Result<(), Error> {
+        if input.len() != self.bin_count {
+            return Err(Error::InputLengthMismatch { expected: self.bin_count, actual: input.len() });
+        }
+        if output.len() != self.ranges.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.ranges.len(), actual: output.len() });
+        }
+
+        for (o, &(start, end)) in output.iter_mut().zip(&self.ranges) {
+            *o = detect(self.detector, &input[start..end]);
+        }
+        Ok(())
+    }
+}
+
+/// 1列分のビンを検波する。
+fn detect(detector: Detector, bins: &[f32]) -> f32 {
+    let values = bins.iter().copied().filter(|v| !v.is_nan());
+    match detector {
+        Detector::PositivePeak => values.reduce(f32::max).unwrap_or(f32::NAN),
+        Detector::NegativePeak => values.reduce(f32::min).unwrap_or(f32::NAN),
+        Detector::Sample => bins[bins.len() / 2],
+        Detector::Average => {
+            let (sum, count) = values.fold((0.0f64, 0), |(sum, count), v| (sum + v as f64, count + 1));
+            if count == 0 {
+                f32::NAN
+            } else {
+                (sum / count as f64) as f32
+            }
+        }
+        Detector::Rms => {
+            // 値は振幅の 10 * log10 なので、電力は 10^(v / 5)
+            let (sum, count) = values.fold((0.0f64, 0), |(sum, count), v| (sum + 10f64.powf(v as f64 / 5.0), count + 1));
+            if count == 0 {
+                f32::NAN
+            } else {
+                (5.0 * (sum / count as f64).log10()) as f32
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimate(input: &[f32], columns: usize, detector: Detector) -> Vec<f32> {
+        let decimator = Decimator::new(input.len(), columns, detector).unwrap();
+        let mut output = vec![0.0; columns];
+        decimator.process(input, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_detectors() {
+        let input = [-80.0, -20.0, -70.0, -60.0, -90.0, -50.0, -40.0, -30.0];
+        assert_eq!(decimate(&input, 2, Detector::PositivePeak), [-20.0, -30.0]);
+        assert_eq!(decimate(&input, 2, Detector::NegativePeak), [-80.0, -90.0]);
+        assert_eq!(decimate(&input, 2, Detector::Sample), [-70.0, -40.0]);
+        assert_eq!(decimate(&input, 2, Detector::Average), [-57.5, -52.5]);
+
+        // RMS は電力で平均するので、最も強いビンに近くなる
+        let rms = decimate(&[-20.0, -20.0, -20.0, -20.0], 1, Detector::Rms);
+        assert!((rms[0] + 20.0).abs() < 1e-4);
+        let rms = decimate(&[0.0, -1000.0], 1, Detector::Rms);
+        // 電力の平均は 1/2 なので 5 * log10(0.5)
+        assert!((rms[0] - 5.0 * 0.5f32.log10()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_uneven_columns() {
+        // 10 ビンを 3 列に: 0..3, 3..6, 6..10
+        let input: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        assert_eq!(decimate(&input, 3, Detector::PositivePeak), [2.0, 5.0, 9.0]);
+        assert_eq!(decimate(&input, 3, Detector::NegativePeak), [0.0, 3.0, 6.0]);
+        // 列数と同じなら変化しない
+        for detector in [Detector::PositivePeak, Detector::Sample, Detector::Average, Detector::Rms] {
+            let output = decimate(&input, 10, detector);
+            assert!(output.iter().zip(&input).all(|(a, b)| (a - b).abs() < 1e-4), "{:?}", detector);
+        }
+    }
+
+    #[test]
+    fn test_more_columns_than_bins() {
+        let input = [1.0, 2.0, 3.0];
+        assert_eq!(decimate(&input, 6, Detector::Average), [1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+        assert_eq!(decimate(&input, 4, Detector::PositivePeak), [1.0, 2.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_no_data() {
+        let input = [f32::NAN, -10.0, f32::NAN, f32::NAN];
+        let output = decimate(&input, 2, Detector::Average);
+        assert_eq!(output[0], -10.0);
+        assert!(output[1].is_nan());
+        assert!(decimate(&input, 2, Detector::Rms)[1].is_nan());
+        assert!(decimate(&input, 2, Detector::PositivePeak)[1].is_nan());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(Decimator::new(0, 1, Detector::Sample).err(), Some(Error::ZeroParameter("Bin count")));
+        assert_eq!(Decimator::new(1, 0, Detector::Sample).err(), Some(Error::ZeroParameter("Column count")));
+        let decimator = Decimator::new(8, 2, Detector::Sample).unwrap();
+        assert_eq!(decimator.process(&[0.0; 4], &mut [0.0; 2]), Err(Error::InputLengthMismatch { expected: 8, actual: 4 }));
+        assert_eq!(decimator.process(&[0.0; 8], &mut [0.0; 3]), Err(Error::OutputLengthMismatch { expected: 2, actual: 3 }));
+    }
+}
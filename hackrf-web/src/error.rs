@@ -0,0 +1,138 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// hackrf-web の処理で発生するエラー。
+///
+/// JS 側には `JsError`（`Error` オブジェクト）として `Display` のメッセージが渡される。
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// FFTサイズが 0
+    ZeroFftSize,
+    /// 窓関数の長さが FFT サイズと一致しない
+    WindowLengthMismatch { expected: usize, actual: usize },
+    /// 校正テーブルに点がない
+    EmptyCalibrationTable,
+    /// 校正テーブルの周波数と補正量の数が一致しない
+    CalibrationLengthMismatch { frequencies: usize, corrections: usize },
+    /// 有限でない値が含まれる
+    NonFiniteValue,
+    /// 区間数が 0
+    ZeroSegmentCount,
+    /// 有効な（有限の）ビンが1つもない
+    NoValidBins,
+    /// 履歴の長さが 0
+    ZeroHistoryLength,
+    /// パーセンタイルが 0 <= noise <= peak <= 1 を満たさない
+    InvalidPercentiles { noise: f32, peak: f32 },
+    /// 入力の長さが期待と一致しない
+    InputLengthMismatch { expected: usize, actual: usize },
+    /// 出力バッファの長さが期待と一致しない
+    OutputLengthMismatch { expected: usize, actual: usize },
+    /// ブロック内の IQ サンプルの範囲がブロックをはみ出す
+    PayloadOutOfBlock { offset: usize, length: usize, stride: usize },
+    /// 指定位置が精密化できる極大ではない
+    NotAPeak(usize),
+    /// パラメータが 0
+    ZeroParameter(&'static str),
+    /// 周波数範囲が low < high を満たさない
+    InvalidFrequencyRange { low: f64, high: f64 },
+    /// USB 転送が失敗した
+    Usb(String),
+    /// ファームウェアの USB API バージョンが要求を満たさない（BCD）
+    UnsupportedUsbApi { required: u16, actual: u16 },
+    /// ゲインが上限を超えている
+    GainOutOfRange { max: u32, actual: u32 },
+    /// 掃引範囲の数が 1..=MAX_SWEEP_RANGES でない
+    InvalidSweepRangeCount(usize),
+    /// 掃引1ステップのバイト数が BYTES_PER_BLOCK の正の倍数でない
+    InvalidSweepNumBytes(u32),
+    /// 対応するベースバンドフィルタがない帯域幅
+    BandwidthOutOfRange(u32),
+    /// 不明な掃引スタイル
+    InvalidSweepStyle(u8),
+    /// 設定できないサンプルレート
+    InvalidSampleRate(f64),
+    /// RBW / VBW が正の有限値でない
+    InvalidFilterBandwidth(f64),
+    /// JSON の読み込みに失敗した
+    Json(String),
+    /// リミットラインの折れ点が不正
+    InvalidLimitLine(String),
+    /// 指定した名前の基準トレースがない
+    UnknownReference(String),
+    /// パラメータが範囲外
+    InvalidParameter { name: &'static str, value: f64 },
+    /// 掃引のセグメントが学習時の周波数軸と一致しない
+    SegmentMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ZeroFftSize => write!(f, "FFT size must be positive, got 0"),
+            Error::WindowLengthMismatch { expected, actual } => {
+                write!(f, "Window size must match FFT size (expected {}, got {})", expected, actual)
+            }
+            Error::EmptyCalibrationTable => write!(f, "Calibration table must have at least one point"),
+            Error::CalibrationLengthMismatch { frequencies, corrections } => write!(
+                f,
+                "Frequency and correction counts must match (frequencies {}, corrections {})",
+                frequencies, corrections
+            ),
+            Error::NonFiniteValue => write!(f, "Values must be finite"),
+            Error::ZeroSegmentCount => write!(f, "Segment count must be positive"),
+            Error::NoValidBins => write!(f, "Input has no valid bins"),
+            Error::ZeroHistoryLength => write!(f, "History length must be positive"),
+            Error::InvalidPercentiles { noise, peak } => {
+                write!(f, "Percentiles must satisfy 0 <= noise <= peak <= 1 (noise {}, peak {})", noise, peak)
+            }
+            Error::InputLengthMismatch { expected, actual } => {
+                write!(f, "Input length must be {}, got {}", expected, actual)
+            }
+            Error::OutputLengthMismatch { expected, actual } => {
+                write!(f, "Output length must be {}, got {}", expected, actual)
+            }
+            Error::PayloadOutOfBlock { offset, length, stride } => write!(
+                f,
+                "Payload (offset {}, length {}) must fit in block stride {}",
+                offset, length, stride
+            ),
+            Error::NotAPeak(index) => write!(f, "No refinable peak at index {}", index),
+            Error::ZeroParameter(name) => write!(f, "{} must be positive", name),
+            Error::InvalidFrequencyRange { low, high } => {
+                write!(f, "Frequency range must satisfy low < high (low {}, high {})", low, high)
+            }
+            Error::Usb(message) => write!(f, "USB transfer failed: {}", message),
+            Error::UnsupportedUsbApi { required, actual } => {
+                write!(f, "USB API version {:x} required, but {:x} found", required, actual)
+            }
+            Error::GainOutOfRange { max, actual } => write!(f, "Gain must be <= {}, got {}", max, actual),
+            Error::InvalidSweepRangeCount(count) => {
+                write!(f, "Sweep range count must be between 1 and {}, got {}", crate::hackrf::MAX_SWEEP_RANGES, count)
+            }
+            Error::InvalidSweepNumBytes(num_bytes) => {
+                write!(f, "Sweep num_bytes must be a positive multiple of {}, got {}", crate::BYTES_PER_BLOCK, num_bytes)
+            }
+            Error::BandwidthOutOfRange(bandwidth) => {
+                write!(f, "No baseband filter for bandwidth {} Hz", bandwidth)
+            }
+            Error::InvalidSweepStyle(style) => write!(f, "Unknown sweep style {}", style),
+            Error::Json(message) => write!(f, "Invalid JSON: {}", message),
+            Error::InvalidLimitLine(message) => write!(f, "Invalid limit line: {}", message),
+            Error::InvalidParameter { name, value } => write!(f, "{} is out of range: {}", name, value),
+            Error::SegmentMismatch => write!(f, "Sweep segments must match the trained frequency axis"),
+            Error::UnknownReference(name) => write!(f, "No reference trace named \"{}\"", name),
+            Error::InvalidFilterBandwidth(bandwidth) => write!(f, "Filter bandwidth must be positive, got {} Hz", bandwidth),
+            Error::InvalidSampleRate(rate) => write!(f, "Sample rate must be between 1 and {} Hz, got {}", u32::MAX, rate),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(e: Error) -> Self {
+        JsError::new(&e.to_string()).into()
+    }
+}
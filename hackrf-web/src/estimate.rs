@@ -0,0 +1,313 @@
+use rustfft::num_complex::Complex;
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, FFT};
+
+/// 放物線補間（対数スペクトル）の偏りの上限 [bin]。
+/// ブラックマン窓などメインローブが広い窓での目安。
+const QUADRATIC_BIAS_BINS: f64 = 0.05;
+/// Jacobsen 推定の偏りの上限 [bin]。矩形窓での目安。
+const JACOBSEN_BIAS_BINS: f64 = 0.01;
+
+/// ビン間隔より細かい周波数推定の結果。
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrequencyEstimate {
+    /// 推定周波数 [Hz]
+    pub frequency_hz: f64,
+    /// 推定の不確かさ（1σ 相当）[Hz]
+    pub uncertainty_hz: f64,
+}
+
+impl FrequencyEstimate {
+    /// DC中心配置でのビン位置 `k + delta` から推定結果を作る。
+    ///
+    /// 不確かさは、手法固有の偏りの上限 `bias_bins` と、
+    /// 単一正弦波の周波数推定の Cramér–Rao 下限から求めた雑音による誤差
+    /// `sqrt(6) / (2π sqrt(snr))` [bin] の二乗和平方根とする。
+    /// `snr` はピークビンの電力とノイズフロア（ビンあたり）の比。
+    fn from_bin(n: usize, k: usize, delta: f64, bias_bins: f64, snr: f64, sample_rate: f64, center_hz: f64) -> Self {
+        let bin_hz = sample_rate / n as f64;
+        let noise_bins = 6.0f64.sqrt() / (2.0 * std::f64::consts::PI * snr.max(1e-12).sqrt());
+        FrequencyEstimate {
+            frequency_hz: center_hz + (k as f64 + delta - (n / 2) as f64) * bin_hz,
+            uncertainty_hz: (bias_bins * bias_bins + noise_bins * noise_bins).sqrt() * bin_hz,
+        }
+    }
+}
+
+/// 3点 `(a, b, c)` を通る放物線の頂点位置（中央の点からのずれ）。
+/// 中央が極大でない場合は `None`。
+fn parabola_vertex(a: f64, b: f64, c: f64) -> Option<f64> {
+    let denom = a - 2.0 * b + c;
+    if denom.is_nan() || denom >= 0.0 || b < a || b < c {
+        return None;
+    }
+    Some(0.5 * (a - c) / denom)
+}
+
+/// 有限値の中央値
+fn median(values: impl Iterator<Item = f64>) -> Result<f64, Error> {
+    let mut values: Vec<f64> = values.filter(|v| v.is_finite()).collect();
+    if values.is_empty() {
+        return Err(Error::NoValidBins);
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    Ok(values[values.len() / 2])
+}
+
+/// dB スペクトル上の極大を、対数スペクトルへの放物線補間で精密化する。
+///
+/// # 引数
+/// * `spectrum_db` - `FFT::fft` の出力（DC中心配置）
+/// * `peak_index` - 極大のビン位置
+/// * `sample_rate` - サンプルレート [Hz]
+/// * `center_hz` - 中心（DC）周波数 [Hz]
+///
+/// # エラー
+/// * `Error::NotAPeak` - `peak_index` が端にある場合や極大でない場合
+#[wasm_bindgen]
+pub fn estimate_quadratic(spectrum_db: &[f32], peak_index: usize, sample_rate: f64, center_hz: f64) -> Result<FrequencyEstimate, Error> {
+    let k = peak_index;
+    if k == 0 || k + 1 >= spectrum_db.len() {
+        return Err(Error::NotAPeak(k));
+    }
+    let (a, b, c) = (spectrum_db[k - 1] as f64, spectrum_db[k] as f64, spectrum_db[k + 1] as f64);
+    let delta = parabola_vertex(a, b, c).ok_or(Error::NotAPeak(k))?;
+
+    // FFT の出力は振幅の 10 * log10 なので、電力比は 2 倍の dB になる
+    let floor = median(spectrum_db.iter().map(|&v| v as f64))?;
+    let snr = 10f64.powf(2.0 * (b - floor) / 10.0);
+    Ok(FrequencyEstimate::from_bin(spectrum_db.len(), k, delta, QUADRATIC_BIAS_BINS, snr, sample_rate, center_hz))
+}
+
+/// 直前の `FFT::fft` の複素ビンに Jacobsen の推定を適用する。
+///
+/// `delta = Re[(X[k-1] - X[k+1]) / (2X[k] - X[k-1] - X[k+1])]`
+///
+/// 矩形窓では偏りがほぼないが、ブラックマン窓などでは偏りが大きくなるため、
+/// その場合は `estimate_quadratic` か `estimate_zoom` を使う。
+///
+/// # 引数
+/// * `fft` - 解析対象のブロックを処理済みの `FFT`
+/// * `peak_index` - 極大のビン位置（DC中心配置）
+/// * `sample_rate` - サンプルレート [Hz]
+/// * `center_hz` - 中心（DC）周波数 [Hz]
+///
+/// # エラー
+/// * `Error::NotAPeak` - `peak_index` が端にある場合や推定値が ±1 ビンを超える場合
+#[wasm_bindgen]
+pub fn estimate_jacobsen(fft: &FFT, peak_index: usize, sample_rate: f64, center_hz: f64) -> Result<FrequencyEstimate, Error> {
+    let n = fft.size();
+    let k = peak_index;
+    if k == 0 || k + 1 >= n {
+        return Err(Error::NotAPeak(k));
+    }
+    let bin = |i: usize| {
+        let c = fft.centered_bin(i);
+        Complex::new(c.re as f64, c.im as f64)
+    };
+    let (prev, peak, next) = (bin(k - 1), bin(k), bin(k + 1));
+    let denom = 2.0 * peak - prev - next;
+    if denom.norm_sqr() == 0.0 {
+        return Err(Error::NotAPeak(k));
+    }
+    let delta = ((prev - next) / denom).re;
+    if !delta.is_finite() || delta.abs() > 1.0 {
+        return Err(Error::NotAPeak(k));
+    }
+
+    let floor = median((0..n).map(|i| bin(i).norm_sqr()))?;
+    let snr = peak.norm_sqr() / floor;
+    Ok(FrequencyEstimate::from_bin(n, k, delta, JACOBSEN_BIAS_BINS, snr, sample_rate, center_hz))
+}
+
+/// 生の IQ サンプルに対するズーム FFT（細かい周波数格子上の DTFT）で極大を精密化する。
+///
+/// `peak_index ± 1` ビンの範囲を 1 ビンあたり `zoom` 点で評価し、
+/// 最大点の周りで放物線補間する。窓関数は `fft` のものを使う。
+///
+/// # 引数
+/// * `fft` - `iq` を処理済みの `FFT`（窓関数とノイズフロアの推定に使う）
+/// * `iq` - `FFT::fft` に渡したものと同じ i8 の IQ 列。長さは `n * 2`
+/// * `peak_index` - 極大のビン位置（DC中心配置）
+/// * `zoom` - 1 ビンあたりの評価点数
+/// * `sample_rate` - サンプルレート [Hz]
+/// * `center_hz` - 中心（DC）周波数 [Hz]
+///
+/// # エラー
+/// * `Error::InputLengthMismatch` - `iq.len() != n * 2` の場合
+/// * `Error::ZeroParameter` - `zoom` が 0 の場合
+/// * `Error::NotAPeak` - `peak_index` が範囲外の場合
+#[wasm_bindgen]
+pub fn estimate_zoom(
+    fft: &FFT,
+    iq: &[i8],
+    peak_index: usize,
+    zoom: usize,
+    sample_rate: f64,
+    center_hz: f64,
+) -> Result<FrequencyEstimate, Error> {
+    let n = fft.size();
+    let k = peak_index;
+    if iq.len() != n * 2 {
+        return Err(Error::InputLengthMismatch { expected: n * 2, actual: iq.len() });
+    }
+    if zoom == 0 {
+        return Err(Error::ZeroParameter("Zoom factor"));
+    }
+    if k >= n {
+        return Err(Error::NotAPeak(k));
+    }
+
+    let window = fft.scaled_window();
+    let samples: Vec<Complex<f64>> = iq
+        .chunks_exact(2)
+        .zip(window.iter())
+        .map(|(s, &w)| Complex::new(s[0] as f64, s[1] as f64) * w as f64)
+        .collect();
+
+    // DC中心配置のビン位置 `pos` における DTFT の電力
+    let power_at = |pos: f64| {
+        let step = Complex::from_polar(1.0, -2.0 * std::f64::consts::PI * (pos - (n / 2) as f64) / n as f64);
+        let mut phasor = Complex::new(1.0, 0.0);
+        let mut acc = Complex::new(0.0, 0.0);
+        for &x in &samples {
+            acc += x * phasor;
+            phasor *= step;
+        }
+        acc.norm_sqr()
+    };
+
+    let step = 1.0 / zoom as f64;
+    let grid: Vec<f64> = (0..=2 * zoom).map(|j| power_at(k as f64 - 1.0 + j as f64 * step)).collect();
+    let (j, _) = grid.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).ok_or(Error::NotAPeak(k))?;
+
+    let mut delta = j as f64 * step - 1.0;
+    if j > 0 && j < grid.len() - 1 {
+        let db = |p: f64| 10.0 * p.max(1e-30).log10();
+        if let Some(d) = parabola_vertex(db(grid[j - 1]), db(grid[j]), db(grid[j + 1])) {
+            delta += d * step;
+        }
+    }
+
+    let floor = median((0..n).map(|i| fft.centered_bin(i).norm_sqr() as f64))?;
+    let snr = grid[j] / floor;
+    Ok(FrequencyEstimate::from_bin(n, k, delta, 0.1 * step, snr, sample_rate, center_hz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 20e6;
+    const CENTER_HZ: f64 = 100e6;
+
+    /// DC から `offset_bins` ビン離れた正弦波（振幅 100）
+    fn tone(n: usize, offset_bins: f64) -> Vec<i8> {
+        let mut iq = vec![0i8; n * 2];
+        for t in 0..n {
+            let phase = 2.0 * std::f64::consts::PI * offset_bins * t as f64 / n as f64;
+            iq[t * 2] = (100.0 * phase.cos()).round() as i8;
+            iq[t * 2 + 1] = (100.0 * phase.sin()).round() as i8;
+        }
+        iq
+    }
+
+    fn blackman(n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| {
+                let x = i as f32 / n as f32;
+                0.42 - 0.5 * (2.0 * std::f32::consts::PI * x).cos() + 0.08 * (4.0 * std::f32::consts::PI * x).cos()
+            })
+            .collect()
+    }
+
+    fn expected_hz(n: usize, offset_bins: f64) -> f64 {
+        CENTER_HZ + offset_bins * SAMPLE_RATE / n as f64
+    }
+
+    fn peak(result: &[f32]) -> usize {
+        result.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap().0
+    }
+
+    #[test]
+    fn test_quadratic_with_blackman_window() {
+        let n = 256;
+        let mut fft = FFT::new(n, &blackman(n));
+        let bin_hz = SAMPLE_RATE / n as f64;
+
+        for &offset in &[10.0, 10.2, 10.5, -30.7] {
+            let mut result = vec![0.0f32; n];
+            fft.fft(&tone(n, offset), &mut result).unwrap();
+
+            let est = estimate_quadratic(&result, peak(&result), SAMPLE_RATE, CENTER_HZ).unwrap();
+            let error = (est.frequency_hz - expected_hz(n, offset)).abs();
+            assert!(error < QUADRATIC_BIAS_BINS * bin_hz, "offset {}: error {} Hz", offset, error);
+            assert!(est.uncertainty_hz >= error, "offset {}: uncertainty {} < error {}", offset, est.uncertainty_hz, error);
+        }
+    }
+
+    #[test]
+    fn test_jacobsen_with_rectangular_window() {
+        let n = 256;
+        let mut fft = FFT::new(n, &vec![1.0; n]);
+        let bin_hz = SAMPLE_RATE / n as f64;
+
+        for &offset in &[20.0, 20.3, -5.45] {
+            let mut result = vec![0.0f32; n];
+            fft.fft(&tone(n, offset), &mut result).unwrap();
+
+            let est = estimate_jacobsen(&fft, peak(&result), SAMPLE_RATE, CENTER_HZ).unwrap();
+            let error = (est.frequency_hz - expected_hz(n, offset)).abs();
+            assert!(error < 0.02 * bin_hz, "offset {}: error {} Hz", offset, error);
+        }
+    }
+
+    #[test]
+    fn test_zoom_with_blackman_window() {
+        let n = 256;
+        let mut fft = FFT::new(n, &blackman(n));
+        let bin_hz = SAMPLE_RATE / n as f64;
+
+        for &offset in &[3.0, 3.37, -64.81] {
+            let iq = tone(n, offset);
+            let mut result = vec![0.0f32; n];
+            fft.fft(&iq, &mut result).unwrap();
+
+            let est = estimate_zoom(&fft, &iq, peak(&result), 16, SAMPLE_RATE, CENTER_HZ).unwrap();
+            let error = (est.frequency_hz - expected_hz(n, offset)).abs();
+            assert!(error < 0.01 * bin_hz, "offset {}: error {} Hz", offset, error);
+            assert!(est.uncertainty_hz < 0.05 * bin_hz);
+        }
+    }
+
+    #[test]
+    fn test_rejects_edges_and_non_peaks() {
+        let spectrum = [-50.0f32, -40.0, -30.0, -20.0];
+        assert_eq!(estimate_quadratic(&spectrum, 0, SAMPLE_RATE, CENTER_HZ).err(), Some(Error::NotAPeak(0)));
+        assert_eq!(estimate_quadratic(&spectrum, 3, SAMPLE_RATE, CENTER_HZ).err(), Some(Error::NotAPeak(3)));
+        // 単調増加の途中は極大ではない
+        assert_eq!(estimate_quadratic(&spectrum, 2, SAMPLE_RATE, CENTER_HZ).err(), Some(Error::NotAPeak(2)));
+
+        let n = 16;
+        let fft = FFT::new(n, &vec![1.0; n]);
+        assert_eq!(
+            estimate_zoom(&fft, &[0i8; 4], 8, 4, SAMPLE_RATE, CENTER_HZ).err(),
+            Some(Error::InputLengthMismatch { expected: 32, actual: 4 })
+        );
+        assert_eq!(
+            estimate_zoom(&fft, &[0i8; 32], 8, 0, SAMPLE_RATE, CENTER_HZ).err(),
+            Some(Error::ZeroParameter("Zoom factor"))
+        );
+    }
+
+    #[test]
+    fn test_uncertainty_grows_with_noise() {
+        let n = 256;
+        let high = FrequencyEstimate::from_bin(n, 128, 0.0, 0.0, 1e6, SAMPLE_RATE, CENTER_HZ);
+        let low = FrequencyEstimate::from_bin(n, 128, 0.0, 0.0, 1e2, SAMPLE_RATE, CENTER_HZ);
+        assert_eq!(high.frequency_hz, CENTER_HZ);
+        assert!(low.uncertainty_hz > high.uncertainty_hz * 10.0);
+    }
+}
@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+
+/// LNA ゲインの最大値 [dB]（8 dB 刻み）
+pub const LNA_GAIN_MAX: u32 = 40;
+/// VGA ゲインの最大値 [dB]（2 dB 刻み）
+pub const VGA_GAIN_MAX: u32 = 62;
+/// RF アンプの公称ゲイン [dB]
+pub const AMP_GAIN_DEFAULT_DB: f32 = 11.0;
+
+/// 受信ゲインの状態から `FFT::fft` の出力を dBm に換算するモデル。
+///
+/// `FFT::fft` の出力は振幅の `10 * log10` であり、電力の dBFS ではない。
+/// そのため出力値を2倍して電力の dBFS とし、そこから総ゲインを引いて
+/// 基準オフセットを加えたものを dBm とする。
+///
+/// 基準オフセットは「全ゲイン 0 dB のとき 0 dBFS に相当する入力電力 [dBm]」であり、
+/// 既知のレベルの信号を使って `calibrate` で求められる。
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GainModel {
+    lna_gain: u32,
+    vga_gain: u32,
+    amp_enable: bool,
+    amp_gain_db: f32,
+    reference_offset_db: f32,
+}
+
+#[wasm_bindgen]
+impl GainModel {
+    /// 全ゲイン 0 dB・アンプ無効の状態でモデルを作成する。
+    ///
+    /// # 引数
+    /// * `reference_offset_db` - 全ゲイン 0 dB のときの 0 dBFS 相当の入力電力 [dBm]
+    #[wasm_bindgen(constructor)]
+    pub fn new(reference_offset_db: f32) -> Self {
+        GainModel {
+            lna_gain: 0,
+            vga_gain: 0,
+            amp_enable: false,
+            amp_gain_db: AMP_GAIN_DEFAULT_DB,
+            reference_offset_db,
+        }
+    }
+
+    /// LNA ゲインを設定する。ファームウェアと同様に 8 dB 刻みへ切り捨て、最大値に制限する。
+    pub fn set_lna_gain(&mut self, value: u32) {
+        self.lna_gain = value.min(LNA_GAIN_MAX) & !0x07;
+    }
+
+    /// VGA ゲインを設定する。ファームウェアと同様に 2 dB 刻みへ切り捨て、最大値に制限する。
+    pub fn set_vga_gain(&mut self, value: u32) {
+        self.vga_gain = value.min(VGA_GAIN_MAX) & !0x01;
+    }
+
+    pub fn set_amp_enable(&mut self, enable: bool) {
+        self.amp_enable = enable;
+    }
+
+    /// RF アンプのゲインを設定する（個体差を実測値で補う場合）。
+    pub fn set_amp_gain_db(&mut self, value: f32) {
+        self.amp_gain_db = value;
+    }
+
+    pub fn set_reference_offset_db(&mut self, value: f32) {
+        self.reference_offset_db = value;
+    }
+
+    pub fn lna_gain(&self) -> u32 {
+        self.lna_gain
+    }
+
+    pub fn vga_gain(&self) -> u32 {
+        self.vga_gain
+    }
+
+    pub fn amp_enable(&self) -> bool {
+        self.amp_enable
+    }
+
+    pub fn reference_offset_db(&self) -> f32 {
+        self.reference_offset_db
+    }
+
+    /// 現在の状態での総ゲイン [dB]
+    pub fn total_gain_db(&self) -> f32 {
+        let amp = if self.amp_enable { self.amp_gain_db } else { 0.0 };
+        self.lna_gain as f32 + self.vga_gain as f32 + amp
+    }
+
+    /// `FFT::fft` の出力値1つを dBm に換算する。
+    pub fn value_to_dbm(&self, value: f32) -> f32 {
+        2.0 * value - self.total_gain_db() + self.reference_offset_db
+    }
+
+    /// 掃引ライン全体を dBm に換算する（in-place）。
+    pub fn to_dbm(&self, line: &mut [f32]) {
+        let offset = self.reference_offset_db - self.total_gain_db();
+        for v in line.iter_mut() {
+            *v = 2.0 * *v + offset;
+        }
+    }
+
+    /// 現在のゲイン状態で `value` と測定された信号が `known_dbm` であるとして、
+    /// 基準オフセットを求め直す。
+    pub fn calibrate(&mut self, value: f32, known_dbm: f32) {
+        self.reference_offset_db = known_dbm - 2.0 * value + self.total_gain_db();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FFT;
+
+    #[test]
+    fn test_gain_quantization() {
+        let mut model = GainModel::new(0.0);
+        model.set_lna_gain(30);
+        model.set_vga_gain(33);
+        assert_eq!(model.lna_gain(), 24);
+        assert_eq!(model.vga_gain(), 32);
+
+        model.set_lna_gain(100);
+        model.set_vga_gain(100);
+        assert_eq!(model.lna_gain(), LNA_GAIN_MAX);
+        assert_eq!(model.vga_gain(), VGA_GAIN_MAX);
+    }
+
+    #[test]
+    fn test_total_gain() {
+        let mut model = GainModel::new(0.0);
+        model.set_lna_gain(16);
+        model.set_vga_gain(20);
+        assert_eq!(model.total_gain_db(), 36.0);
+        model.set_amp_enable(true);
+        assert_eq!(model.total_gain_db(), 36.0 + AMP_GAIN_DEFAULT_DB);
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let mut model = GainModel::new(0.0);
+        model.set_lna_gain(16);
+        model.set_vga_gain(20);
+        model.calibrate(-10.0, -50.0);
+        assert!((model.value_to_dbm(-10.0) + 50.0).abs() < 1e-5);
+
+        let mut line = vec![-10.0f32, -20.0];
+        model.to_dbm(&mut line);
+        assert!((line[0] + 50.0).abs() < 1e-5);
+        // FFT 出力の 10 dB は電力で 20 dB
+        assert!((line[1] + 70.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_level_is_stable_across_gain_change() {
+        // 同じ入力電力の信号を、ゲインを 8 dB 変えて受信した場合を模擬する。
+        // 8 dB は振幅比 10^(8/20) ≈ 2.512 なので、20 → 50 とする。
+        let n = 16;
+        let window = vec![1.0; n];
+        let mut fft = FFT::new(n, &window);
+
+        let measure = |fft: &mut FFT, amplitude: i8| {
+            let mut input = vec![0i8; n * 2];
+            for i in 0..n {
+                input[i * 2] = amplitude;
+            }
+            let mut result = vec![0.0f32; n];
+            fft.fft(&input, &mut result).unwrap();
+            result[n / 2]
+        };
+
+        let mut model = GainModel::new(-10.0);
+        model.set_lna_gain(8);
+        model.set_vga_gain(20);
+        let low = model.value_to_dbm(measure(&mut fft, 20));
+
+        model.set_lna_gain(16);
+        let high = model.value_to_dbm(measure(&mut fft, 50));
+
+        assert!((low - high).abs() < 0.1, "level moved from {} to {} dBm", low, high);
+    }
+}
@@ -0,0 +1,495 @@
+//! HackRF の USB プロトコル（hackrf.js の移植）。
+//!
+//! ベンダーリクエストの組み立てとレスポンスの解釈だけを行い、実際の転送は
+//! `Transport` に任せる。ブラウザでは `WebUsbTransport`、テストでは `MockTransport` や
+//! 実機なしで掃引データを生成する `SimulatedDevice` を使う。
+
+mod plan;
+mod rate;
+mod simulator;
+mod transport;
+#[cfg(target_arch = "wasm32")]
+mod webusb;
+
+pub(crate) use plan::step_frequencies;
+pub use plan::{SweepPlan, FREQ_MAX_MHZ};
+pub use rate::{SampleRate, MAX_SAMPLE_RATE_DIVIDER};
+pub use simulator::{Carrier, Scene, SimulatedDevice, SimulatedHackRF};
+pub use transport::{ControlRequest, MockCall, MockTransport, Transport};
+#[cfg(target_arch = "wasm32")]
+pub use webusb::WebUsbTransport;
+
+pub(crate) use transport::block_on;
+
+use crate::Error;
+
+/// HackRF の USB ベンダー ID / プロダクト ID（HackRF One, Jawbreaker, rad1o, DFU）
+pub const USB_IDS: [(u16, u16); 4] = [(0x1d50, 0x604b), (0x1d50, 0x6089), (0x1d50, 0xcc15), (0x1fc9, 0x000c)];
+pub const USB_CONFIG_STANDARD: u8 = 0x1;
+/// 受信データのバルクエンドポイント
+pub const RX_ENDPOINT: u8 = 1;
+/// 1回のバルク転送で受け取るバイト数
+pub const TRANSFER_BUFFER_SIZE: usize = 262144;
+pub const SAMPLES_PER_BLOCK: usize = 8192;
+pub const MAX_SWEEP_RANGES: usize = 10;
+
+/// ベンダーリクエスト番号
+pub mod request {
+    pub const SET_TRANSCEIVER_MODE: u8 = 1;
+    pub const MAX2837_WRITE: u8 = 2;
+    pub const MAX2837_READ: u8 = 3;
+    pub const SI5351C_WRITE: u8 = 4;
+    pub const SI5351C_READ: u8 = 5;
+    pub const SAMPLE_RATE_SET: u8 = 6;
+    pub const BASEBAND_FILTER_BANDWIDTH_SET: u8 = 7;
+    pub const RFFC5071_WRITE: u8 = 8;
+    pub const RFFC5071_READ: u8 = 9;
+    pub const SPIFLASH_ERASE: u8 = 10;
+    pub const SPIFLASH_WRITE: u8 = 11;
+    pub const SPIFLASH_READ: u8 = 12;
+    pub const BOARD_ID_READ: u8 = 14;
+    pub const VERSION_STRING_READ: u8 = 15;
+    pub const SET_FREQ: u8 = 16;
+    pub const AMP_ENABLE: u8 = 17;
+    pub const BOARD_PARTID_SERIALNO_READ: u8 = 18;
+    pub const SET_LNA_GAIN: u8 = 19;
+    pub const SET_VGA_GAIN: u8 = 20;
+    pub const SET_TXVGA_GAIN: u8 = 21;
+    pub const ANTENNA_ENABLE: u8 = 23;
+    pub const SET_FREQ_EXPLICIT: u8 = 24;
+    pub const USB_WCID_VENDOR_REQ: u8 = 25;
+    pub const INIT_SWEEP: u8 = 26;
+    pub const OPERACAKE_GET_BOARDS: u8 = 27;
+    pub const OPERACAKE_SET_PORTS: u8 = 28;
+    pub const SET_HW_SYNC_MODE: u8 = 29;
+    pub const RESET: u8 = 30;
+    pub const OPERACAKE_SET_RANGES: u8 = 31;
+    pub const CLKOUT_ENABLE: u8 = 32;
+    pub const SPIFLASH_STATUS: u8 = 33;
+    pub const SPIFLASH_CLEAR_STATUS: u8 = 34;
+    pub const OPERACAKE_GPIO_TEST: u8 = 35;
+    pub const CPLD_CHECKSUM: u8 = 36;
+    pub const UI_ENABLE: u8 = 37;
+    pub const OPERACAKE_SET_MODE: u8 = 38;
+    pub const OPERACAKE_GET_MODE: u8 = 39;
+    pub const OPERACAKE_SET_DWELL_TIMES: u8 = 40;
+    pub const GET_M0_STATE: u8 = 41;
+    pub const SET_TX_UNDERRUN_LIMIT: u8 = 42;
+    pub const SET_RX_OVERRUN_LIMIT: u8 = 43;
+    pub const GET_CLKIN_STATUS: u8 = 44;
+    pub const BOARD_REV_READ: u8 = 45;
+    pub const SUPPORTED_PLATFORM_READ: u8 = 46;
+    pub const SET_LEDS: u8 = 47;
+    pub const SET_USER_BIAS_T_OPTS: u8 = 48;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum TransceiverMode {
+    Off = 0,
+    Receive = 1,
+    Transmit = 2,
+    Ss = 3,
+    CpldUpdate = 4,
+    RxSweep = 5,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SweepStyle {
+    Linear = 0,
+    Interleaved = 1,
+}
+
+/// MAX2837 のベースバンドフィルタの帯域幅 [Hz]
+pub const MAX2837_FT: [u32; 16] = [
+    1_750_000, 2_500_000, 3_500_000, 5_000_000, 5_500_000, 6_000_000, 7_000_000, 8_000_000, 9_000_000, 10_000_000,
+    12_000_000, 14_000_000, 15_000_000, 20_000_000, 24_000_000, 28_000_000,
+];
+
+/// 指定の帯域幅に対して設定するベースバンドフィルタの帯域幅を選ぶ（hackrf.js と同じ規則）。
+///
+/// `bandwidth_hz` 以上の最初のフィルタの1つ下を選ぶ。最初のフィルタ以下なら最初のフィルタ。
+///
+/// # エラー
+/// * `Error::BandwidthOutOfRange` - `bandwidth_hz` が最大のフィルタを超える場合
+pub fn compute_baseband_filter_bw(bandwidth_hz: u32) -> Result<u32, Error> {
+    let i = MAX2837_FT
+        .iter()
+        .position(|&ft| ft >= bandwidth_hz)
+        .ok_or(Error::BandwidthOutOfRange(bandwidth_hz))?;
+    Ok(MAX2837_FT[i.saturating_sub(1)])
+}
+
+/// ボードのパーツ ID とシリアル番号
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartIdSerialNo {
+    pub part_id: [u32; 2],
+    pub serial_no: [u32; 4],
+}
+
+/// HackRF デバイス。プロトコルの操作を `Transport` 越しに行う。
+#[derive(Debug)]
+pub struct HackRF<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> HackRF<T> {
+    pub fn new(transport: T) -> Self {
+        HackRF { transport }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// ファームウェアの USB API バージョン（BCD、例: 0x0107）
+    pub fn usb_api_version(&self) -> u16 {
+        self.transport.device_version()
+    }
+
+    fn usb_api_required(&self, required: u16) -> Result<(), Error> {
+        let actual = self.usb_api_version();
+        if actual < required {
+            return Err(Error::UnsupportedUsbApi { required, actual });
+        }
+        Ok(())
+    }
+
+    async fn control_in(&self, request: u8, value: u16, index: u16, length: u16) -> Result<Vec<u8>, Error> {
+        let data = self.transport.control_in(ControlRequest::new(request, value, index), length).await?;
+        if data.len() < length as usize {
+            return Err(Error::Usb(format!("short response to request {} ({} of {} bytes)", request, data.len(), length)));
+        }
+        Ok(data)
+    }
+
+    async fn control_out(&self, request: u8, value: u16, index: u16, data: &[u8]) -> Result<(), Error> {
+        self.transport.control_out(ControlRequest::new(request, value, index), data).await
+    }
+
+    pub async fn board_id_read(&self) -> Result<u8, Error> {
+        Ok(self.control_in(request::BOARD_ID_READ, 0, 0, 1).await?[0])
+    }
+
+    /// ファームウェアのバージョン文字列
+    pub async fn version_string_read(&self) -> Result<String, Error> {
+        let data = self.transport.control_in(ControlRequest::new(request::VERSION_STRING_READ, 0, 0), 255).await?;
+        Ok(data.iter().map(|&b| b as char).collect())
+    }
+
+    pub async fn board_partid_serialno_read(&self) -> Result<PartIdSerialNo, Error> {
+        let data = self.control_in(request::BOARD_PARTID_SERIALNO_READ, 0, 0, 24).await?;
+        let word = |i: usize| u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
+        Ok(PartIdSerialNo {
+            part_id: [word(0), word(1)],
+            serial_no: [word(2), word(3), word(4), word(5)],
+        })
+    }
+
+    /// ボードのリビジョン。USB API 0x0106 以降が必要。
+    pub async fn board_rev_read(&self) -> Result<u8, Error> {
+        self.usb_api_required(0x0106)?;
+        Ok(self.control_in(request::BOARD_REV_READ, 0, 0, 1).await?[0])
+    }
+
+    pub async fn set_transceiver_mode(&self, mode: TransceiverMode) -> Result<(), Error> {
+        self.control_out(request::SET_TRANSCEIVER_MODE, mode as u16, 0, &[]).await
+    }
+
+    /// サンプルレートを `freq_hz / divider` に設定し、それに合うベースバンドフィルタを設定する。
+    pub async fn set_sample_rate_manual(&self, freq_hz: u32, divider: u32) -> Result<(), Error> {
+        let mut params = [0u8; 8];
+        params[..4].copy_from_slice(&freq_hz.to_le_bytes());
+        params[4..].copy_from_slice(&divider.to_le_bytes());
+        self.control_out(request::SAMPLE_RATE_SET, 0, 0, &params).await?;
+
+        let bandwidth = SampleRate { freq_hz, divider }.baseband_filter_bw()?;
+        self.set_baseband_filter_bandwidth(bandwidth).await
+    }
+
+    /// 任意のサンプルレートを設定する（libhackrf の `hackrf_set_sample_rate` に相当）。
+    ///
+    /// `SampleRate::new` で選んだ `freq_hz / divider` を `set_sample_rate_manual` で設定する。
+    /// FFT のビン幅などには、要求したレートではなく戻り値の `rate_hz()` を使うこと。
+    ///
+    /// # 戻り値
+    /// 実際に設定したサンプルレート
+    pub async fn set_sample_rate(&self, rate_hz: f64) -> Result<SampleRate, Error> {
+        let rate = SampleRate::new(rate_hz)?;
+        self.set_sample_rate_manual(rate.freq_hz, rate.divider).await?;
+        Ok(rate)
+    }
+
+    pub async fn set_baseband_filter_bandwidth(&self, bandwidth_hz: u32) -> Result<(), Error> {
+        self.control_out(
+            request::BASEBAND_FILTER_BANDWIDTH_SET,
+            (bandwidth_hz & 0xffff) as u16,
+            (bandwidth_hz >> 16) as u16,
+            &[],
+        )
+        .await
+    }
+
+    /// VGA（ベースバンド）ゲインを設定する。2 dB 刻みに切り捨てる。
+    ///
+    /// # エラー
+    /// * `Error::GainOutOfRange` - `value` が 62 を超える場合
+    pub async fn set_vga_gain(&self, value: u32) -> Result<(), Error> {
+        if value > crate::gain::VGA_GAIN_MAX {
+            return Err(Error::GainOutOfRange { max: crate::gain::VGA_GAIN_MAX, actual: value });
+        }
+        self.set_gain(request::SET_VGA_GAIN, value & !0x01).await
+    }
+
+    /// LNA（IF）ゲインを設定する。8 dB 刻みに切り捨てる。
+    ///
+    /// # エラー
+    /// * `Error::GainOutOfRange` - `value` が 40 を超える場合
+    pub async fn set_lna_gain(&self, value: u32) -> Result<(), Error> {
+        if value > crate::gain::LNA_GAIN_MAX {
+            return Err(Error::GainOutOfRange { max: crate::gain::LNA_GAIN_MAX, actual: value });
+        }
+        self.set_gain(request::SET_LNA_GAIN, value & !0x07).await
+    }
+
+    /// ゲイン設定のリクエスト。ファームウェアは成功時に 0 以外の1バイトを返す。
+    async fn set_gain(&self, request: u8, value: u32) -> Result<(), Error> {
+        let data = self.control_in(request, 0, value as u16, 1).await?;
+        if data[0] == 0 {
+            return Err(Error::Usb(format!("gain {} rejected (request {})", value, request)));
+        }
+        Ok(())
+    }
+
+    pub async fn set_amp_enable(&self, enable: bool) -> Result<(), Error> {
+        self.control_out(request::AMP_ENABLE, enable as u16, 0, &[]).await
+    }
+
+    pub async fn set_antenna_enable(&self, enable: bool) -> Result<(), Error> {
+        self.control_out(request::ANTENNA_ENABLE, enable as u16, 0, &[]).await
+    }
+
+    pub async fn reset(&self) -> Result<(), Error> {
+        self.control_out(request::RESET, 0, 0, &[]).await
+    }
+
+    /// 中心周波数を設定する。ペイロードは MHz 部と Hz 部の u32 2つ。
+    pub async fn set_freq(&self, freq_hz: u64) -> Result<(), Error> {
+        let freq_mhz = (freq_hz / 1_000_000) as u32;
+        let freq_hz0 = (freq_hz % 1_000_000) as u32;
+        let mut data = [0u8; 8];
+        data[..4].copy_from_slice(&freq_mhz.to_le_bytes());
+        data[4..].copy_from_slice(&freq_hz0.to_le_bytes());
+        self.control_out(request::SET_FREQ, 0, 0, &data).await
+    }
+
+    /// 掃引モードを設定する。
+    ///
+    /// 範囲や転送サイズの検証は `SweepPlan::new` で済んでいる。
+    pub async fn init_sweep(&self, plan: &SweepPlan) -> Result<(), Error> {
+        let setup = plan.request();
+        self.control_out(setup.request, setup.value, setup.index, &plan.payload()).await
+    }
+
+    /// 受信を開始する。以後 `read_transfer` でデータを読む。
+    pub async fn start_rx(&self) -> Result<(), Error> {
+        self.set_transceiver_mode(TransceiverMode::Receive).await
+    }
+
+    /// 掃引モードで受信を開始する。USB API 0x0104 以降が必要。
+    pub async fn start_rx_sweep(&self) -> Result<(), Error> {
+        self.usb_api_required(0x0104)?;
+        self.set_transceiver_mode(TransceiverMode::RxSweep).await
+    }
+
+    /// 受信データを1転送分読む（最大 `TRANSFER_BUFFER_SIZE` バイト）。
+    pub async fn read_transfer(&self) -> Result<Vec<u8>, Error> {
+        self.transport.bulk_in(RX_ENDPOINT, TRANSFER_BUFFER_SIZE).await
+    }
+
+    pub async fn stop_rx(&self) -> Result<(), Error> {
+        self.set_transceiver_mode(TransceiverMode::Off).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> HackRF<MockTransport> {
+        HackRF::new(MockTransport::new(0x0107))
+    }
+
+    fn last_call(hackrf: &HackRF<MockTransport>) -> MockCall {
+        hackrf.transport().calls().pop().unwrap()
+    }
+
+    #[test]
+    fn test_compute_baseband_filter_bw() {
+        assert_eq!(compute_baseband_filter_bw(0), Ok(1_750_000));
+        assert_eq!(compute_baseband_filter_bw(1_750_000), Ok(1_750_000));
+        // hackrf.js と同じく、一致するフィルタがあっても1つ下を選ぶ
+        assert_eq!(compute_baseband_filter_bw(15_000_000), Ok(14_000_000));
+        assert_eq!(compute_baseband_filter_bw(15_000_001), Ok(15_000_000));
+        assert_eq!(compute_baseband_filter_bw(28_000_001), Err(Error::BandwidthOutOfRange(28_000_001)));
+    }
+
+    #[test]
+    fn test_set_freq() {
+        let hackrf = device();
+        block_on(hackrf.set_freq(2_400_123_456)).unwrap();
+        let mut data = 2400u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&123_456u32.to_le_bytes());
+        assert_eq!(
+            last_call(&hackrf),
+            MockCall::ControlOut { request: ControlRequest::new(request::SET_FREQ, 0, 0), data }
+        );
+    }
+
+    #[test]
+    fn test_set_sample_rate_manual() {
+        let hackrf = device();
+        block_on(hackrf.set_sample_rate_manual(20_000_000, 1)).unwrap();
+        let calls = hackrf.transport().calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[0],
+            MockCall::ControlOut {
+                request: ControlRequest::new(request::SAMPLE_RATE_SET, 0, 0),
+                data: vec![0x00, 0x2d, 0x31, 0x01, 1, 0, 0, 0],
+            }
+        );
+        // 0.75 * 20 MHz = 15 MHz → 14 MHz のフィルタ
+        let bw = 14_000_000u32;
+        assert_eq!(
+            calls[1],
+            MockCall::ControlOut {
+                request: ControlRequest::new(request::BASEBAND_FILTER_BANDWIDTH_SET, (bw & 0xffff) as u16, (bw >> 16) as u16),
+                data: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_sample_rate() {
+        let hackrf = device();
+        let rate = block_on(hackrf.set_sample_rate(10e6 / 3.0)).unwrap();
+        assert_eq!(rate, SampleRate { freq_hz: 10_000_000, divider: 3 });
+        let calls = hackrf.transport().calls();
+        assert_eq!(
+            calls[0],
+            MockCall::ControlOut {
+                request: ControlRequest::new(request::SAMPLE_RATE_SET, 0, 0),
+                data: vec![0x80, 0x96, 0x98, 0x00, 3, 0, 0, 0],
+            }
+        );
+        // 0.75 * 3.33 MHz = 2.5 MHz → 1.75 MHz のフィルタ
+        assert_eq!(
+            calls[1],
+            MockCall::ControlOut { request: ControlRequest::new(request::BASEBAND_FILTER_BANDWIDTH_SET, 0xb3f0, 0x1a), data: vec![] }
+        );
+    }
+
+    #[test]
+    fn test_gains() {
+        let hackrf = device();
+        // ファームウェアが 0 を返したら失敗
+        assert!(matches!(block_on(hackrf.set_lna_gain(16)), Err(Error::Usb(_))));
+
+        hackrf.transport().set_control_in_response(request::SET_LNA_GAIN, vec![1]);
+        hackrf.transport().set_control_in_response(request::SET_VGA_GAIN, vec![1]);
+        block_on(hackrf.set_lna_gain(30)).unwrap();
+        assert_eq!(
+            last_call(&hackrf),
+            MockCall::ControlIn { request: ControlRequest::new(request::SET_LNA_GAIN, 0, 24), length: 1 }
+        );
+        block_on(hackrf.set_vga_gain(33)).unwrap();
+        assert_eq!(
+            last_call(&hackrf),
+            MockCall::ControlIn { request: ControlRequest::new(request::SET_VGA_GAIN, 0, 32), length: 1 }
+        );
+
+        hackrf.transport().clear_calls();
+        assert_eq!(block_on(hackrf.set_vga_gain(63)), Err(Error::GainOutOfRange { max: 62, actual: 63 }));
+        assert_eq!(block_on(hackrf.set_lna_gain(41)), Err(Error::GainOutOfRange { max: 40, actual: 41 }));
+        assert!(hackrf.transport().calls().is_empty());
+    }
+
+    #[test]
+    fn test_init_sweep() {
+        let hackrf = device();
+        let plan = SweepPlan::new(&[(2400, 2500)], 16384, 20_000_000, 7_500_000, SweepStyle::Interleaved).unwrap();
+        block_on(hackrf.init_sweep(&plan)).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(&20_000_000u32.to_le_bytes());
+        data.extend_from_slice(&7_500_000u32.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&[0x60, 0x09, 0xc4, 0x09]);
+        assert_eq!(
+            last_call(&hackrf),
+            MockCall::ControlOut { request: ControlRequest::new(request::INIT_SWEEP, 16384, 0), data }
+        );
+    }
+
+    #[test]
+    fn test_board_info() {
+        let hackrf = device();
+        hackrf.transport().set_control_in_response(request::BOARD_ID_READ, vec![2]);
+        assert_eq!(block_on(hackrf.board_id_read()), Ok(2));
+
+        hackrf.transport().set_control_in_response(request::VERSION_STRING_READ, b"2024.02.1".to_vec());
+        assert_eq!(block_on(hackrf.version_string_read()).unwrap(), "2024.02.1");
+
+        let data: Vec<u8> = (1..=6u32).flat_map(|w| w.to_le_bytes()).collect();
+        hackrf.transport().set_control_in_response(request::BOARD_PARTID_SERIALNO_READ, data);
+        assert_eq!(
+            block_on(hackrf.board_partid_serialno_read()),
+            Ok(PartIdSerialNo { part_id: [1, 2], serial_no: [3, 4, 5, 6] })
+        );
+
+        // 応答が短い場合はエラー
+        hackrf.transport().set_control_in_response(request::BOARD_PARTID_SERIALNO_READ, vec![0; 4]);
+        assert!(matches!(block_on(hackrf.board_partid_serialno_read()), Err(Error::Usb(_))));
+    }
+
+    #[test]
+    fn test_usb_api_version() {
+        let old = HackRF::new(MockTransport::new(0x0103));
+        assert_eq!(
+            block_on(old.start_rx_sweep()),
+            Err(Error::UnsupportedUsbApi { required: 0x0104, actual: 0x0103 })
+        );
+        assert_eq!(
+            block_on(old.board_rev_read()),
+            Err(Error::UnsupportedUsbApi { required: 0x0106, actual: 0x0103 })
+        );
+        assert!(old.transport().calls().is_empty());
+
+        let hackrf = device();
+        block_on(hackrf.start_rx_sweep()).unwrap();
+        assert_eq!(
+            last_call(&hackrf),
+            MockCall::ControlOut {
+                request: ControlRequest::new(request::SET_TRANSCEIVER_MODE, TransceiverMode::RxSweep as u16, 0),
+                data: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_transfer() {
+        let hackrf = device();
+        hackrf.transport().push_bulk_in(vec![0x7f; 16]);
+        assert_eq!(block_on(hackrf.read_transfer()), Ok(vec![0x7f; 16]));
+        assert_eq!(last_call(&hackrf), MockCall::BulkIn { endpoint: RX_ENDPOINT, length: TRANSFER_BUFFER_SIZE });
+        assert!(block_on(hackrf.read_transfer()).is_err());
+
+        hackrf.transport().fail_request(request::SET_TRANSCEIVER_MODE);
+        assert!(matches!(block_on(hackrf.stop_rx()), Err(Error::Usb(_))));
+    }
+}
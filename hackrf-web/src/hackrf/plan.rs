@@ -0,0 +1,434 @@
+use super::{request, ControlRequest, SweepStyle, MAX_SWEEP_RANGES};
+use crate::{Error, BYTES_PER_BLOCK};
+
+/// 掃引できる周波数の上限 [MHz]（hackrf_sweep と同じ）
+pub const FREQ_MAX_MHZ: u16 = 7250;
+
+/// `init_sweep` ベンダーリクエストの内容。
+///
+/// `new` は範囲を検証し、周波数順に並べ、重なる（または接する）範囲をまとめ、
+/// 各範囲の終端をステップ幅の整数倍になるよう延ばす（hackrf_sweep と同じ）。
+/// `payload` と `request` が libhackrf の `hackrf_init_sweep` と同じ転送内容を返す。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SweepPlan {
+    ranges_mhz: Vec<(u16, u16)>,
+    step_width: u32,
+    num_bytes: u32,
+    offset: u32,
+    style: SweepStyle,
+}
+
+impl SweepPlan {
+    /// 掃引計画を作成する。
+    ///
+    /// # 引数
+    /// * `ranges_mhz` - 掃引範囲 (開始, 終了) [MHz] のリスト。順序は任意で、重なっていてもよい
+    /// * `num_bytes` - 1ステップで受信するバイト数。`BYTES_PER_BLOCK` の倍数
+    /// * `step_width` - ステップ幅 [Hz]。通常はサンプルレート
+    /// * `offset` - 中心周波数のオフセット [Hz]。通常はサンプルレートの 3/8
+    /// * `style` - ステップの並べ方
+    ///
+    /// # エラー
+    /// * `Error::InvalidFrequencyRange` - 開始 >= 終了、または終了（ステップ幅の整数倍に延ばした後）が
+    ///   `FREQ_MAX_MHZ` を超える場合
+    /// * `Error::InvalidSweepRangeCount` - まとめた後の範囲の数が 1..=`MAX_SWEEP_RANGES` でない場合
+    /// * `Error::InvalidSweepNumBytes` - `num_bytes` が `BYTES_PER_BLOCK` の正の倍数でない場合
+    /// * `Error::ZeroParameter` - `step_width` が 0 の場合
+    pub fn new(ranges_mhz: &[(u16, u16)], num_bytes: u32, step_width: u32, offset: u32, style: SweepStyle) -> Result<SweepPlan, Error> {
+        validate_parameters(step_width, num_bytes)?;
+        if ranges_mhz.is_empty() {
+            return Err(Error::InvalidSweepRangeCount(0));
+        }
+        for &(start, stop) in ranges_mhz {
+            if start >= stop || stop > FREQ_MAX_MHZ {
+                return Err(range_error(start, stop));
+            }
+        }
+
+        let mut sorted = ranges_mhz.to_vec();
+        sorted.sort_unstable();
+
+        let mut merged: Vec<(u16, u16)> = Vec::with_capacity(sorted.len());
+        for (start, stop) in sorted {
+            match merged.last_mut() {
+                // 延ばした終端まで届いていればまとめる
+                Some((s, e)) if start <= *e => *e = round_stop(*s, stop.max(*e), step_width),
+                _ => merged.push((start, round_stop(start, stop, step_width))),
+            }
+        }
+
+        // 延ばした終端もファームウェアに送るので、上限を超えてはいけない
+        if let Some(&(start, stop)) = merged.iter().find(|r| r.1 > FREQ_MAX_MHZ) {
+            return Err(range_error(start, stop));
+        }
+        if merged.len() > MAX_SWEEP_RANGES {
+            return Err(Error::InvalidSweepRangeCount(merged.len()));
+        }
+
+        Ok(SweepPlan {
+            ranges_mhz: merged,
+            step_width,
+            num_bytes,
+            offset,
+            style,
+        })
+    }
+
+    /// 転送内容から掃引計画を復元する（範囲はまとめず、そのまま使う）。
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - ペイロードの長さが 9 + 範囲数 * 4 の形でない場合
+    /// * `Error::InvalidSweepStyle` - 掃引スタイルが不明な場合
+    /// * その他 `new` と同じ検証によるエラー
+    pub fn decode(request: ControlRequest, payload: &[u8]) -> Result<SweepPlan, Error> {
+        let ranges_len = payload.len().saturating_sub(9);
+        if payload.len() < 9 + 4 || !ranges_len.is_multiple_of(4) {
+            return Err(Error::InputLengthMismatch {
+                expected: 9 + ranges_len.div_ceil(4).max(1) * 4,
+                actual: payload.len(),
+            });
+        }
+
+        let num_bytes = request.value as u32 | (request.index as u32) << 16;
+        let step_width = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        let offset = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+        let style = match payload[8] {
+            0 => SweepStyle::Linear,
+            1 => SweepStyle::Interleaved,
+            other => return Err(Error::InvalidSweepStyle(other)),
+        };
+        validate_parameters(step_width, num_bytes)?;
+
+        let ranges_mhz: Vec<(u16, u16)> = payload[9..]
+            .chunks_exact(4)
+            .map(|r| (u16::from_le_bytes([r[0], r[1]]), u16::from_le_bytes([r[2], r[3]])))
+            .collect();
+        if ranges_mhz.len() > MAX_SWEEP_RANGES {
+            return Err(Error::InvalidSweepRangeCount(ranges_mhz.len()));
+        }
+        if let Some(&(start, stop)) = ranges_mhz.iter().find(|r| r.0 >= r.1 || r.1 > FREQ_MAX_MHZ) {
+            return Err(range_error(start, stop));
+        }
+
+        Ok(SweepPlan {
+            ranges_mhz,
+            step_width,
+            num_bytes,
+            offset,
+            style,
+        })
+    }
+
+    /// 掃引範囲 (開始, 終了) [MHz]。周波数順で重ならない
+    pub fn ranges_mhz(&self) -> &[(u16, u16)] {
+        &self.ranges_mhz
+    }
+
+    pub fn step_width(&self) -> u32 {
+        self.step_width
+    }
+
+    pub fn num_bytes(&self) -> u32 {
+        self.num_bytes
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn style(&self) -> SweepStyle {
+        self.style
+    }
+
+    /// 範囲 `range` のステップ数（ステップ幅単位）
+    pub fn steps_in_range(&self, range: usize) -> u64 {
+        let (start, stop) = self.ranges_mhz[range];
+        ((stop - start) as u64 * 1_000_000).div_ceil(self.step_width as u64)
+    }
+
+    /// 1回の掃引でのチューニング回数（`step_frequencies` の数）。
+    ///
+    /// インターリーブではほぼステップ数の2倍になるが、1/4 と 3/4 のステップ幅は整数に切り捨てて
+    /// 進むため、ステップ幅が 4 の倍数でないと範囲の終端付近で1つ多くなることがある。
+    pub fn tunings_per_sweep(&self) -> u64 {
+        self.ranges_hz().map(|(start, stop)| range_frequencies(start, stop, self.step_width as u64, self.style).count() as u64).sum()
+    }
+
+    /// 1回の掃引でファームウェアが出力するブロックヘッダの周波数 [Hz]（出力の順）
+    pub fn step_frequencies(&self) -> Vec<u64> {
+        step_frequencies(self.ranges_hz(), self.step_width as u64, self.style)
+    }
+
+    fn ranges_hz(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.ranges_mhz.iter().map(|&(start, stop)| (start as u64 * 1_000_000, stop as u64 * 1_000_000))
+    }
+
+    /// コントロール転送のセットアップ（wValue / wIndex に `num_bytes` の下位 / 上位 16 ビット）
+    pub fn request(&self) -> ControlRequest {
+        ControlRequest::new(request::INIT_SWEEP, (self.num_bytes & 0xffff) as u16, (self.num_bytes >> 16) as u16)
+    }
+
+    /// コントロール転送のデータ。
+    ///
+    /// step_width (u32 LE), offset (u32 LE), style (u8) に続き、各範囲の開始と終了 (u16 LE)。
+    pub fn payload(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(9 + self.ranges_mhz.len() * 4);
+        data.extend_from_slice(&self.step_width.to_le_bytes());
+        data.extend_from_slice(&self.offset.to_le_bytes());
+        data.push(self.style as u8);
+        for &(start, stop) in &self.ranges_mhz {
+            data.extend_from_slice(&start.to_le_bytes());
+            data.extend_from_slice(&stop.to_le_bytes());
+        }
+        data
+    }
+}
+
+/// 範囲 `ranges_hz` を順に掃引したときのステップの周波数 [Hz]。
+///
+/// ファームウェアと同じく、線形ではステップ幅ずつ、インターリーブでは 1/4 と 3/4 ステップ幅を
+/// 交互に進み、範囲の終端以上になったら次の範囲に移る。
+pub(crate) fn step_frequencies(ranges_hz: impl IntoIterator<Item = (u64, u64)>, step_width: u64, style: SweepStyle) -> Vec<u64> {
+    ranges_hz.into_iter().flat_map(|(start, stop)| range_frequencies(start, stop, step_width, style)).collect()
+}
+
+/// 1つの範囲 `start..stop` [Hz] のステップの周波数
+fn range_frequencies(start: u64, stop: u64, step_width: u64, style: SweepStyle) -> impl Iterator<Item = u64> {
+    let mut long = false;
+    std::iter::successors(Some(start), move |&frequency| {
+        let next = frequency
+            + match style {
+                SweepStyle::Linear => step_width,
+                SweepStyle::Interleaved if long => step_width * 3 / 4,
+                SweepStyle::Interleaved => step_width / 4,
+            };
+        long = !long;
+        Some(next)
+    })
+    .take_while(move |&frequency| frequency < stop)
+}
+
+fn validate_parameters(step_width: u32, num_bytes: u32) -> Result<(), Error> {
+    if num_bytes == 0 || !num_bytes.is_multiple_of(BYTES_PER_BLOCK as u32) {
+        return Err(Error::InvalidSweepNumBytes(num_bytes));
+    }
+    if step_width == 0 {
+        return Err(Error::ZeroParameter("Step width"));
+    }
+    Ok(())
+}
+
+/// 範囲の終端 [MHz] を、ステップ数を変えずに済む最大の値まで延ばす。
+///
+/// ステップ幅が 1 MHz の倍数なら、開始からステップ幅の整数倍の位置になる。
+/// 結果は `FREQ_MAX_MHZ` + `u32::MAX` Hz 未満なので u16 に収まる。
+fn round_stop(start: u16, stop: u16, step_width: u32) -> u16 {
+    let steps = ((stop - start) as u64 * 1_000_000).div_ceil(step_width as u64);
+    start + (steps * step_width as u64 / 1_000_000) as u16
+}
+
+fn range_error(start: u16, stop: u16) -> Error {
+    Error::InvalidFrequencyRange { low: start as f64 * 1e6, high: stop as f64 * 1e6 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_range() {
+        // script.js と同じく、スパンをステップ幅の倍数に延ばす
+        let plan = SweepPlan::new(&[(2400, 2450)], 16384, 20_000_000, 7_500_000, SweepStyle::Interleaved).unwrap();
+        assert_eq!(plan.ranges_mhz(), &[(2400, 2460)]);
+        assert_eq!(plan.steps_in_range(0), 3);
+        assert_eq!(plan.tunings_per_sweep(), 6);
+        assert_eq!(
+            plan.step_frequencies(),
+            vec![2_400_000_000, 2_405_000_000, 2_420_000_000, 2_425_000_000, 2_440_000_000, 2_445_000_000]
+        );
+        assert_eq!(plan.request(), ControlRequest::new(request::INIT_SWEEP, 16384, 0));
+        assert_eq!(
+            plan.payload(),
+            vec![0x00, 0x2d, 0x31, 0x01, 0xe0, 0x70, 0x72, 0x00, 0x01, 0x60, 0x09, 0x9c, 0x09]
+        );
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let plan = SweepPlan::new(&[(2400, 2410), (433, 434), (2405, 2450), (868, 870), (453, 460)], 16384, 20_000_000, 0, SweepStyle::Linear)
+            .unwrap();
+        // 433-434 は 433-453 に延び、453-460 に接するのでまとまる
+        assert_eq!(plan.ranges_mhz(), &[(433, 473), (868, 888), (2400, 2460)]);
+        assert_eq!(plan.tunings_per_sweep(), 2 + 1 + 3);
+    }
+
+    #[test]
+    fn test_tunings_with_truncated_quarter_steps() {
+        // 116023 / 4 + 116023 * 3 / 4 = 116022 なので、1往復ごとに 1 Hz ずつ遅れて
+        // 終端の手前にステップが1つ多く入る
+        let plan = SweepPlan::new(&[(1, 22)], 16384, 116_023, 0, SweepStyle::Interleaved).unwrap();
+        assert_eq!(plan.steps_in_range(0), 181);
+        assert_eq!(plan.step_frequencies().len(), 363);
+        assert_eq!(plan.tunings_per_sweep(), 363);
+    }
+
+    #[test]
+    fn test_invalid_plans() {
+        let plan = |ranges: &[(u16, u16)], num_bytes, step| SweepPlan::new(ranges, num_bytes, step, 0, SweepStyle::Linear);
+        assert_eq!(plan(&[], 16384, 20_000_000), Err(Error::InvalidSweepRangeCount(0)));
+        assert_eq!(
+            plan(&[(100, 100)], 16384, 20_000_000),
+            Err(Error::InvalidFrequencyRange { low: 100e6, high: 100e6 })
+        );
+        assert_eq!(
+            plan(&[(7000, 7300)], 16384, 20_000_000),
+            Err(Error::InvalidFrequencyRange { low: 7000e6, high: 7300e6 })
+        );
+        // 7240..7250 は 20 MHz ステップで 7260 まで延びる
+        assert_eq!(
+            plan(&[(7240, 7250)], 16384, 20_000_000),
+            Err(Error::InvalidFrequencyRange { low: 7240e6, high: 7260e6 })
+        );
+        assert_eq!(plan(&[(7230, 7250)], 16384, 20_000_000).unwrap().ranges_mhz(), &[(7230, 7250)]);
+        assert_eq!(plan(&[(1, 2)], 16384, 0), Err(Error::ZeroParameter("Step width")));
+        assert_eq!(plan(&[(1, 2)], 16383, 1), Err(Error::InvalidSweepNumBytes(16383)));
+
+        let disjoint: Vec<(u16, u16)> = (0..11).map(|i| (i * 100, i * 100 + 10)).collect();
+        assert_eq!(plan(&disjoint, 16384, 20_000_000), Err(Error::InvalidSweepRangeCount(11)));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let setup = ControlRequest::new(request::INIT_SWEEP, 16384, 0);
+        assert!(matches!(SweepPlan::decode(setup, &[0; 9]), Err(Error::InputLengthMismatch { .. })));
+        assert!(matches!(SweepPlan::decode(setup, &[0; 15]), Err(Error::InputLengthMismatch { .. })));
+
+        let mut payload = SweepPlan::new(&[(1, 2)], 16384, 1_000_000, 0, SweepStyle::Linear).unwrap().payload();
+        payload[8] = 7;
+        assert_eq!(SweepPlan::decode(setup, &payload), Err(Error::InvalidSweepStyle(7)));
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// libhackrf の `hackrf_init_sweep` と同じ手順で転送内容を組み立てる。
+    ///
+    /// ```c
+    /// data[0] = step_width & 0xff; ... data[3] = (step_width >> 24) & 0xff;
+    /// data[4] = offset & 0xff; ... data[7] = (offset >> 24) & 0xff;
+    /// data[8] = style;
+    /// for (i = 0; i < (2 * num_ranges); i++) {
+    ///     data[9 + i * 2] = frequency_list[i] & 0xff;
+    ///     data[10 + i * 2] = (frequency_list[i] >> 8) & 0xff;
+    /// }
+    /// libusb_control_transfer(..., HACKRF_VENDOR_REQUEST_INIT_SWEEP,
+    ///     num_bytes & 0xffff, (num_bytes >> 16) & 0xffff, data, size, 0);
+    /// ```
+    fn libhackrf_init_sweep(frequency_list: &[u16], num_bytes: u32, step_width: u32, offset: u32, style: u8) -> (u16, u16, Vec<u8>) {
+        let mut data = vec![0u8; 9 + frequency_list.len() * 2];
+        for i in 0..4 {
+            data[i] = (step_width >> (8 * i)) as u8;
+            data[4 + i] = (offset >> (8 * i)) as u8;
+        }
+        data[8] = style;
+        for (i, &f) in frequency_list.iter().enumerate() {
+            data[9 + i * 2] = (f & 0xff) as u8;
+            data[10 + i * 2] = (f >> 8) as u8;
+        }
+        ((num_bytes & 0xffff) as u16, ((num_bytes >> 16) & 0xffff) as u16, data)
+    }
+
+    /// 延ばした終端が `FREQ_MAX_MHZ` を超えない範囲（帯域の上端は `rounded_stop_stays_in_band` で試す）
+    fn ranges() -> impl Strategy<Value = Vec<(u16, u16)>> {
+        prop::collection::vec((1u16..6500, 1u16..250).prop_map(|(start, len)| (start, start + len)), 1..8)
+    }
+
+    fn step_width() -> impl Strategy<Value = u32> {
+        prop_oneof![Just(20_000_000u32), (1u32..40).prop_map(|m| m * 1_000_000), 100_000u32..30_000_000]
+    }
+
+    fn style() -> impl Strategy<Value = SweepStyle> {
+        prop_oneof![Just(SweepStyle::Linear), Just(SweepStyle::Interleaved)]
+    }
+
+    proptest! {
+        #[test]
+        fn payload_matches_libhackrf(
+            ranges in ranges(),
+            step in step_width(),
+            blocks in 1u32..300,
+            offset in any::<u32>(),
+            style in style(),
+        ) {
+            let num_bytes = blocks * BYTES_PER_BLOCK as u32;
+            let plan = SweepPlan::new(&ranges, num_bytes, step, offset, style).unwrap();
+            let frequency_list: Vec<u16> = plan.ranges_mhz().iter().flat_map(|&(s, e)| [s, e]).collect();
+            let (value, index, data) = libhackrf_init_sweep(&frequency_list, num_bytes, step, offset, style as u8);
+
+            let request = plan.request();
+            prop_assert_eq!(request.request, request::INIT_SWEEP);
+            prop_assert_eq!(request.value, value);
+            prop_assert_eq!(request.index, index);
+            prop_assert_eq!(plan.payload(), data);
+            prop_assert_eq!(SweepPlan::decode(request, &plan.payload()), Ok(plan));
+        }
+
+        #[test]
+        fn merged_ranges_cover_input(ranges in ranges(), step in step_width(), style in style()) {
+            let plan = SweepPlan::new(&ranges, 16384, step, 0, style).unwrap();
+            let merged = plan.ranges_mhz();
+
+            // 周波数順で、延ばした終端同士も重ならない
+            for pair in merged.windows(2) {
+                prop_assert!(pair[0].1 < pair[1].0, "{:?}", merged);
+            }
+            // 入力の範囲はどれかにすべて含まれる
+            for &(start, stop) in &ranges {
+                prop_assert!(merged.iter().any(|&(s, e)| s <= start && stop <= e), "{:?} not in {:?}", (start, stop), merged);
+            }
+            // 各範囲はステップが覆う幅を超えない最大の MHz 単位の幅
+            for (i, &(s, e)) in merged.iter().enumerate() {
+                let span = (e - s) as u64 * 1_000_000;
+                let covered = plan.steps_in_range(i) * step as u64;
+                prop_assert!(span <= covered && covered < span + 1_000_000, "{:?} step {}", (s, e), step);
+            }
+            prop_assert_eq!(plan.step_frequencies().len() as u64, plan.tunings_per_sweep());
+        }
+
+        #[test]
+        fn rounded_stop_stays_in_band(
+            start in (FREQ_MAX_MHZ - 300)..FREQ_MAX_MHZ,
+            len in 1u16..300,
+            step in step_width(),
+            style in style(),
+        ) {
+            let stop = (start + len).min(FREQ_MAX_MHZ);
+            prop_assume!(start < stop);
+            let rounded = round_stop(start, stop, step);
+            match SweepPlan::new(&[(start, stop)], 16384, step, 0, style) {
+                Ok(plan) => {
+                    prop_assert_eq!(plan.ranges_mhz(), &[(start, rounded)]);
+                    prop_assert!(rounded <= FREQ_MAX_MHZ);
+                    prop_assert!(SweepPlan::decode(plan.request(), &plan.payload()).is_ok());
+                }
+                Err(e) => {
+                    prop_assert!(rounded > FREQ_MAX_MHZ, "{:?} rounded to {}", (start, stop), rounded);
+                    prop_assert_eq!(e, range_error(start, rounded));
+                }
+            }
+        }
+
+        #[test]
+        fn decode_rejects_invalid_payload(len in 0usize..60, style in 2u8..=255) {
+            let setup = ControlRequest::new(request::INIT_SWEEP, 16384, 0);
+            let mut payload = vec![1u8; len];
+            if len > 8 {
+                payload[8] = style;
+            }
+            prop_assert!(SweepPlan::decode(setup, &payload).is_err());
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use super::compute_baseband_filter_bw;
+use crate::Error;
+
+/// 分周比の上限（libhackrf の `MAX_N` 未満）
+pub const MAX_SAMPLE_RATE_DIVIDER: u32 = 31;
+
+/// `SAMPLE_RATE_SET` で設定するサンプルレート `freq_hz / divider`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleRate {
+    pub freq_hz: u32,
+    pub divider: u32,
+}
+
+impl SampleRate {
+    /// 要求されたサンプルレートに最も近い `freq_hz / divider` を求める
+    /// （libhackrf の `hackrf_set_sample_rate` に相当）。
+    ///
+    /// 分周比 1..=`MAX_SAMPLE_RATE_DIVIDER` のうち誤差が最小のもの（同じなら小さい分周比）を選ぶ。
+    /// 整数のレートは分周比 1 で正確に、10e6 / 3 のような有理数のレートも正確に表せる。
+    ///
+    /// # エラー
+    /// * `Error::InvalidSampleRate` - `rate_hz` が正の有限値でない、または u32 の Hz に収まらない場合
+    pub fn new(rate_hz: f64) -> Result<SampleRate, Error> {
+        if !rate_hz.is_finite() || rate_hz < 1.0 || rate_hz > u32::MAX as f64 {
+            return Err(Error::InvalidSampleRate(rate_hz));
+        }
+
+        let mut best = SampleRate { freq_hz: rate_hz.round() as u32, divider: 1 };
+        let mut best_error = (best.rate_hz() - rate_hz).abs();
+        for divider in 2..=MAX_SAMPLE_RATE_DIVIDER {
+            let freq = (rate_hz * divider as f64).round();
+            if freq > u32::MAX as f64 {
+                break;
+            }
+            let candidate = SampleRate { freq_hz: freq as u32, divider };
+            let error = (candidate.rate_hz() - rate_hz).abs();
+            if error < best_error {
+                best = candidate;
+                best_error = error;
+            }
+        }
+        Ok(best)
+    }
+
+    /// 実際のサンプルレート [Hz]
+    pub fn rate_hz(&self) -> f64 {
+        self.freq_hz as f64 / self.divider as f64
+    }
+
+    /// このレートに合わせるベースバンドフィルタ（レートの 0.75 倍から hackrf.js と同じ規則で選ぶ）
+    ///
+    /// # エラー
+    /// * `Error::BandwidthOutOfRange` - レートが高すぎて対応するフィルタがない場合
+    pub fn baseband_filter_bw(&self) -> Result<u32, Error> {
+        compute_baseband_filter_bw((0.75 * self.rate_hz()) as u32)
+    }
+
+    /// `fft_size` 点の FFT のビン幅 [Hz]
+    pub fn bin_width_hz(&self, fft_size: usize) -> f64 {
+        self.rate_hz() / fft_size as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_rates() {
+        assert_eq!(SampleRate::new(20e6).unwrap(), SampleRate { freq_hz: 20_000_000, divider: 1 });
+        assert_eq!(SampleRate::new(10e6 / 3.0).unwrap(), SampleRate { freq_hz: 10_000_000, divider: 3 });
+        assert_eq!(SampleRate::new(2.5e6 + 0.5).unwrap(), SampleRate { freq_hz: 5_000_001, divider: 2 });
+        // 8 MHz / 7 = 1142857.142857...
+        let rate = SampleRate::new(8e6 / 7.0).unwrap();
+        assert_eq!(rate, SampleRate { freq_hz: 8_000_000, divider: 7 });
+        assert_eq!(rate.rate_hz(), 8e6 / 7.0);
+    }
+
+    #[test]
+    fn test_inexact_rate() {
+        // 分周比 31 以下では表せないので、誤差が最小のものを使う
+        let requested = 12_345_678.901_234;
+        let rate = SampleRate::new(requested).unwrap();
+        assert!(rate.divider <= MAX_SAMPLE_RATE_DIVIDER);
+        for divider in 1..=MAX_SAMPLE_RATE_DIVIDER {
+            let freq = (requested * divider as f64).round();
+            assert!((rate.rate_hz() - requested).abs() <= (freq / divider as f64 - requested).abs());
+        }
+        assert!((rate.rate_hz() - requested).abs() < 0.5 / rate.divider as f64 + 1e-9);
+    }
+
+    #[test]
+    fn test_baseband_filter() {
+        assert_eq!(SampleRate::new(20e6).unwrap().baseband_filter_bw(), Ok(14_000_000));
+        assert_eq!(SampleRate::new(10e6).unwrap().baseband_filter_bw(), Ok(7_000_000));
+        assert_eq!(SampleRate::new(2e6).unwrap().baseband_filter_bw(), Ok(1_750_000));
+        assert_eq!(SampleRate::new(10e6).unwrap().bin_width_hz(1000), 10_000.0);
+    }
+
+    #[test]
+    fn test_invalid_rates() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 5e9] {
+            assert!(matches!(SampleRate::new(rate), Err(Error::InvalidSampleRate(_))), "{}", rate);
+        }
+    }
+}
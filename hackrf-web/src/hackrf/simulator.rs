@@ -0,0 +1,703 @@
+use std::cell::RefCell;
+use std::f64::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use super::{block_on, request, ControlRequest, HackRF, SweepPlan, SweepStyle, TransceiverMode, Transport, MAX2837_FT};
+use crate::gain::{AMP_GAIN_DEFAULT_DB, LNA_GAIN_MAX, VGA_GAIN_MAX};
+use crate::{Error, BLOCK_HEADER_LEN, BYTES_PER_BLOCK};
+
+/// 模擬する信号源（連続波）
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Carrier {
+    /// 周波数 [Hz]
+    pub frequency_hz: f64,
+    /// 総ゲイン 0 dB のときのレベル [dBFS]
+    pub level_db: f32,
+}
+
+/// `SimulatedDevice` が受信する電波環境。
+///
+/// レベルは総ゲイン（LNA + VGA + アンプ）0 dB のときの dBFS（振幅 127 が 0 dBFS）で表し、
+/// 受信時には総ゲインが加わる。ADC の範囲を超えた値はクリップされる。
+/// DC スパイクは ADC 側のオフセットなのでゲインの影響を受けない。
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub carriers: Vec<Carrier>,
+    /// 複素ガウス雑音の電力 [dBFS]（総ゲイン 0 dB）
+    pub noise_db: f32,
+    /// DC オフセット（I, Q）[ADC の LSB]
+    pub dc_offset: (f32, f32),
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            carriers: Vec::new(),
+            noise_db: -70.0,
+            dc_offset: (2.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    mode: TransceiverMode,
+    sample_rate: f64,
+    baseband_filter_hz: u32,
+    center_hz: u64,
+    lna_gain: u32,
+    vga_gain: u32,
+    amp_enable: bool,
+    sweep: Option<SweepPlan>,
+    /// 次に出力する掃引ステップのヘッダ周波数 [Hz]
+    sweep_freq: u64,
+    sweep_range: usize,
+    /// インターリーブ掃引で次のステップが 3/4 ステップ幅の移動か
+    interleave_long: bool,
+    /// 生成したサンプルの通し番号（搬送波の位相に使う）
+    sample_index: u64,
+    rng: Rng,
+}
+
+impl State {
+    fn total_gain_db(&self) -> f32 {
+        self.lna_gain as f32 + self.vga_gain as f32 + if self.amp_enable { AMP_GAIN_DEFAULT_DB } else { 0.0 }
+    }
+}
+
+/// ハードウェアなしで HackRF の受信を模擬する `Transport`。
+///
+/// `HackRF` から送られるベンダーリクエスト（サンプルレート、周波数、ゲイン、`init_sweep`、
+/// 送受信モード）を解釈し、バルク転送では `Scene` から合成した IQ サンプルを返す。
+/// 掃引モードではファームウェアと同じく各ブロックの先頭に `0x7F 0x7F` と周波数のヘッダを置き、
+/// `num_bytes` ごとにステップを進める。
+///
+/// 雑音は固定のシードから生成するので、同じ操作に対して同じデータを返す。
+#[derive(Debug)]
+pub struct SimulatedDevice {
+    scene: RefCell<Scene>,
+    state: RefCell<State>,
+}
+
+impl SimulatedDevice {
+    pub fn new(scene: Scene) -> Self {
+        SimulatedDevice {
+            scene: RefCell::new(scene),
+            state: RefCell::new(State {
+                mode: TransceiverMode::Off,
+                sample_rate: 10e6,
+                baseband_filter_hz: MAX2837_FT[MAX2837_FT.len() - 1],
+                center_hz: 0,
+                lna_gain: 0,
+                vga_gain: 0,
+                amp_enable: false,
+                sweep: None,
+                sweep_freq: 0,
+                sweep_range: 0,
+                interleave_long: false,
+                sample_index: 0,
+                rng: Rng(0x2545_f491_4f6c_dd1d),
+            }),
+        }
+    }
+
+    /// 電波環境を差し替える（受信中でもよい）。
+    pub fn set_scene(&self, scene: Scene) {
+        *self.scene.borrow_mut() = scene;
+    }
+
+    /// 現在の総ゲイン [dB]
+    pub fn total_gain_db(&self) -> f32 {
+        self.state.borrow().total_gain_db()
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.state.borrow().sample_rate
+    }
+
+    pub fn baseband_filter_bandwidth(&self) -> u32 {
+        self.state.borrow().baseband_filter_hz
+    }
+
+    pub fn transceiver_mode(&self) -> TransceiverMode {
+        self.state.borrow().mode
+    }
+
+    /// `center_hz` を中心に `out.len() / 2` サンプルを合成して書き込む。
+    fn synthesize(&self, state: &mut State, center_hz: f64, out: &mut [u8]) {
+        let scene = self.scene.borrow();
+        let gain = 10f64.powf(state.total_gain_db() as f64 / 20.0);
+        let noise_sigma = 10f64.powf(scene.noise_db as f64 / 20.0) * 127.0 * gain / 2f64.sqrt();
+
+        // ナイキスト帯域内の搬送波だけを (振幅, 位相の増分) にしておく
+        let tones: Vec<(f64, f64)> = scene
+            .carriers
+            .iter()
+            .map(|c| (c.frequency_hz - center_hz, c.level_db))
+            .filter(|&(offset, _)| offset.abs() < state.sample_rate / 2.0)
+            .map(|(offset, level)| (10f64.powf(level as f64 / 20.0) * 127.0 * gain, 2.0 * PI * offset / state.sample_rate))
+            .collect();
+
+        for iq in out.chunks_exact_mut(2) {
+            let t = state.sample_index as f64;
+            let (mut re, mut im) = state.rng.gaussian_pair();
+            re *= noise_sigma;
+            im *= noise_sigma;
+            for &(amplitude, omega) in &tones {
+                let phase = (omega * t) % (2.0 * PI);
+                re += amplitude * phase.cos();
+                im += amplitude * phase.sin();
+            }
+            re += scene.dc_offset.0 as f64;
+            im += scene.dc_offset.1 as f64;
+            iq[0] = re.round().clamp(-128.0, 127.0) as i8 as u8;
+            iq[1] = im.round().clamp(-128.0, 127.0) as i8 as u8;
+            state.sample_index += 1;
+        }
+    }
+
+    /// 掃引モードのデータを `length` バイト（ブロック単位）生成する。
+    fn sweep_transfer(&self, length: usize) -> Result<Vec<u8>, Error> {
+        let mut state = self.state.borrow_mut();
+        let sweep = state.sweep.clone().ok_or_else(|| Error::Usb("sweep is not initialized".to_string()))?;
+        let blocks_per_step = (sweep.num_bytes() as usize / BYTES_PER_BLOCK).max(1);
+
+        let blocks = length / BYTES_PER_BLOCK;
+        let mut data = vec![0u8; blocks * BYTES_PER_BLOCK];
+        for (i, block) in data.chunks_exact_mut(BYTES_PER_BLOCK).enumerate() {
+            let frequency = state.sweep_freq;
+            self.synthesize(&mut state, (frequency + sweep.offset() as u64) as f64, block);
+            block[0] = 0x7F;
+            block[1] = 0x7F;
+            block[2..BLOCK_HEADER_LEN].copy_from_slice(&frequency.to_le_bytes());
+
+            if (i + 1) % blocks_per_step == 0 {
+                advance_sweep(&mut state, &sweep);
+            }
+        }
+        Ok(data)
+    }
+
+    fn handle_control_out(&self, request: ControlRequest, data: &[u8]) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        match request.request {
+            request::SET_TRANSCEIVER_MODE => {
+                state.mode = match request.value {
+                    0 => TransceiverMode::Off,
+                    1 => TransceiverMode::Receive,
+                    5 if state.sweep.is_some() => TransceiverMode::RxSweep,
+                    _ => return Err(stall(request)),
+                };
+                if state.mode == TransceiverMode::RxSweep {
+                    let sweep = state.sweep.clone().unwrap();
+                    state.sweep_range = 0;
+                    state.sweep_freq = sweep.ranges_mhz()[0].0 as u64 * 1_000_000;
+                    state.interleave_long = false;
+                }
+            }
+            request::SAMPLE_RATE_SET => {
+                let freq = read_u32(data, 0).ok_or_else(|| stall(request))?;
+                let divider = read_u32(data, 4).ok_or_else(|| stall(request))?;
+                if freq == 0 || divider == 0 {
+                    return Err(stall(request));
+                }
+                state.sample_rate = freq as f64 / divider as f64;
+            }
+            request::BASEBAND_FILTER_BANDWIDTH_SET => {
+                state.baseband_filter_hz = request.value as u32 | (request.index as u32) << 16;
+            }
+            request::SET_FREQ => {
+                let mhz = read_u32(data, 0).ok_or_else(|| stall(request))?;
+                let hz = read_u32(data, 4).ok_or_else(|| stall(request))?;
+                state.center_hz = mhz as u64 * 1_000_000 + hz as u64;
+            }
+            request::AMP_ENABLE => state.amp_enable = request.value != 0,
+            request::ANTENNA_ENABLE | request::RESET => {}
+            request::INIT_SWEEP => {
+                state.sweep = Some(SweepPlan::decode(request, data).map_err(|_| stall(request))?);
+            }
+            _ => return Err(stall(request)),
+        }
+        Ok(())
+    }
+
+    fn handle_control_in(&self, request: ControlRequest, length: u16) -> Result<Vec<u8>, Error> {
+        let mut state = self.state.borrow_mut();
+        let mut data = match request.request {
+            request::BOARD_ID_READ => vec![2],
+            request::BOARD_REV_READ => vec![0x85],
+            request::VERSION_STRING_READ => b"simulated".to_vec(),
+            request::BOARD_PARTID_SERIALNO_READ => vec![0; 24],
+            // ゲインはファームウェアと同じく範囲外なら 0（失敗）を返す
+            request::SET_LNA_GAIN => {
+                let value = request.index as u32;
+                if value > LNA_GAIN_MAX {
+                    vec![0]
+                } else {
+                    state.lna_gain = value & !0x07;
+                    vec![1]
+                }
+            }
+            request::SET_VGA_GAIN => {
+                let value = request.index as u32;
+                if value > VGA_GAIN_MAX {
+                    vec![0]
+                } else {
+                    state.vga_gain = value & !0x01;
+                    vec![1]
+                }
+            }
+            _ => return Err(stall(request)),
+        };
+        data.truncate(length as usize);
+        Ok(data)
+    }
+}
+
+impl Transport for SimulatedDevice {
+    async fn control_in(&self, request: ControlRequest, length: u16) -> Result<Vec<u8>, Error> {
+        self.handle_control_in(request, length)
+    }
+
+    async fn control_out(&self, request: ControlRequest, data: &[u8]) -> Result<(), Error> {
+        self.handle_control_out(request, data)
+    }
+
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        if endpoint != super::RX_ENDPOINT {
+            return Err(Error::Usb(format!("no such endpoint {}", endpoint)));
+        }
+        let mode = self.state.borrow().mode;
+        match mode {
+            TransceiverMode::RxSweep => self.sweep_transfer(length),
+            TransceiverMode::Receive => {
+                let mut state = self.state.borrow_mut();
+                let center = state.center_hz as f64;
+                let mut data = vec![0u8; length & !1];
+                self.synthesize(&mut state, center, &mut data);
+                Ok(data)
+            }
+            _ => Err(Error::Usb("device is not receiving".to_string())),
+        }
+    }
+
+    fn device_version(&self) -> u16 {
+        0x0107
+    }
+}
+
+/// `SimulatedDevice` につないだ `HackRF` を JS から操作する。
+///
+/// worker.js は実機の hackrf.js `HackRF` の代わりにこれを開ける（simulator.js）ので、
+/// USB デバイスなしで掃引から表示までを動かせる。
+/// `SimulatedDevice` の Future はすぐに完了するので、各操作は同期的に行う。
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct SimulatedHackRF {
+    hackrf: HackRF<SimulatedDevice>,
+    scene: Scene,
+}
+
+#[wasm_bindgen]
+impl SimulatedHackRF {
+    /// 搬送波のない電波環境で模擬デバイスを作成する。
+    ///
+    /// # 引数
+    /// * `noise_db` - 複素ガウス雑音の電力 [dBFS]（総ゲイン 0 dB）
+    #[wasm_bindgen(constructor)]
+    pub fn new(noise_db: f32) -> SimulatedHackRF {
+        let scene = Scene { noise_db, ..Scene::default() };
+        SimulatedHackRF {
+            hackrf: HackRF::new(SimulatedDevice::new(scene.clone())),
+            scene,
+        }
+    }
+
+    /// 搬送波を追加する（受信中でもよい）。
+    ///
+    /// # 引数
+    /// * `frequency_hz` - 周波数 [Hz]
+    /// * `level_db` - 総ゲイン 0 dB のときのレベル [dBFS]
+    pub fn add_carrier(&mut self, frequency_hz: f64, level_db: f32) {
+        self.scene.carriers.push(Carrier { frequency_hz, level_db });
+        self.hackrf.transport().set_scene(self.scene.clone());
+    }
+
+    /// 搬送波をすべて取り除く。
+    pub fn clear_carriers(&mut self) {
+        self.scene.carriers.clear();
+        self.hackrf.transport().set_scene(self.scene.clone());
+    }
+
+    /// 模擬デバイスの現在の総ゲイン [dB]
+    pub fn total_gain_db(&self) -> f32 {
+        self.hackrf.transport().total_gain_db()
+    }
+
+    pub fn board_id_read(&self) -> Result<u8, Error> {
+        block_on(self.hackrf.board_id_read())
+    }
+
+    pub fn version_string_read(&self) -> Result<String, Error> {
+        block_on(self.hackrf.version_string_read())
+    }
+
+    /// USB API バージョン（BCD、例: 0x0107）
+    pub fn usb_api_version(&self) -> u16 {
+        self.hackrf.usb_api_version()
+    }
+
+    /// パート ID 2 語とシリアル番号 4 語を続けたもの
+    pub fn board_partid_serialno_read(&self) -> Result<Vec<u32>, Error> {
+        let id = block_on(self.hackrf.board_partid_serialno_read())?;
+        Ok(id.part_id.iter().chain(id.serial_no.iter()).copied().collect())
+    }
+
+    pub fn board_rev_read(&self) -> Result<u8, Error> {
+        block_on(self.hackrf.board_rev_read())
+    }
+
+    pub fn set_sample_rate_manual(&self, freq_hz: u32, divider: u32) -> Result<(), Error> {
+        block_on(self.hackrf.set_sample_rate_manual(freq_hz, divider))
+    }
+
+    pub fn set_baseband_filter_bandwidth(&self, bandwidth_hz: u32) -> Result<(), Error> {
+        block_on(self.hackrf.set_baseband_filter_bandwidth(bandwidth_hz))
+    }
+
+    pub fn set_lna_gain(&self, value: u32) -> Result<(), Error> {
+        block_on(self.hackrf.set_lna_gain(value))
+    }
+
+    pub fn set_vga_gain(&self, value: u32) -> Result<(), Error> {
+        block_on(self.hackrf.set_vga_gain(value))
+    }
+
+    pub fn set_amp_enable(&self, enable: bool) -> Result<(), Error> {
+        block_on(self.hackrf.set_amp_enable(enable))
+    }
+
+    pub fn set_antenna_enable(&self, enable: bool) -> Result<(), Error> {
+        block_on(self.hackrf.set_antenna_enable(enable))
+    }
+
+    /// 中心周波数を設定する。JS の number で渡せるよう `f64` で受け取る
+    pub fn set_freq(&self, freq_hz: f64) -> Result<(), Error> {
+        block_on(self.hackrf.set_freq(freq_hz as u64))
+    }
+
+    /// 掃引を設定する（hackrf.js の `initSweep` と同じ引数）。
+    ///
+    /// # 引数
+    /// * `ranges_mhz` - 掃引範囲の (下端, 上端) [MHz] を平坦に並べたもの
+    /// * `style` - 掃引スタイル（`SweepStyle` の値）
+    ///
+    /// # エラー
+    /// * `Error::InvalidParameter` - `ranges_mhz` の長さが奇数の場合
+    /// * `Error::InvalidSweepStyle` - 掃引スタイルが不明な場合
+    /// * その他 `SweepPlan::new` と同じ
+    pub fn init_sweep(&self, ranges_mhz: &[u16], num_bytes: u32, step_width: u32, offset: u32, style: u8) -> Result<(), Error> {
+        if !ranges_mhz.len().is_multiple_of(2) {
+            return Err(Error::InvalidParameter { name: "Range list length", value: ranges_mhz.len() as f64 });
+        }
+        let style = match style {
+            0 => SweepStyle::Linear,
+            1 => SweepStyle::Interleaved,
+            other => return Err(Error::InvalidSweepStyle(other)),
+        };
+        let ranges: Vec<(u16, u16)> = ranges_mhz.chunks_exact(2).map(|r| (r[0], r[1])).collect();
+        let plan = SweepPlan::new(&ranges, num_bytes, step_width, offset, style)?;
+        block_on(self.hackrf.init_sweep(&plan))
+    }
+
+    pub fn start_rx(&self) -> Result<(), Error> {
+        block_on(self.hackrf.start_rx())
+    }
+
+    /// 掃引受信を始める。先に `init_sweep` が必要。
+    pub fn start_rx_sweep(&self) -> Result<(), Error> {
+        block_on(self.hackrf.start_rx_sweep())
+    }
+
+    /// 1回のバルク転送分（`TRANSFER_BUFFER_SIZE` バイト）の受信データ
+    pub fn read_transfer(&self) -> Result<Vec<u8>, Error> {
+        block_on(self.hackrf.read_transfer())
+    }
+
+    pub fn stop_rx(&self) -> Result<(), Error> {
+        block_on(self.hackrf.stop_rx())
+    }
+}
+
+/// 掃引を1ステップ進める（ファームウェアの sweep モードと同じ順序）。
+///
+/// 線形ではステップ幅ずつ、インターリーブでは 1/4 と 3/4 ステップ幅を交互に進む。
+/// 範囲の終端に達したら次の範囲（最後なら最初の範囲）の開始周波数に戻る。
+fn advance_sweep(state: &mut State, sweep: &SweepPlan) {
+    let step = sweep.step_width() as u64;
+    state.sweep_freq += match sweep.style() {
+        SweepStyle::Linear => step,
+        SweepStyle::Interleaved => {
+            let delta = if state.interleave_long { step * 3 / 4 } else { step / 4 };
+            state.interleave_long = !state.interleave_long;
+            delta
+        }
+    };
+    if state.sweep_freq >= sweep.ranges_mhz()[state.sweep_range].1 as u64 * 1_000_000 {
+        state.sweep_range = (state.sweep_range + 1) % sweep.ranges_mhz().len();
+        state.sweep_freq = sweep.ranges_mhz()[state.sweep_range].0 as u64 * 1_000_000;
+        state.interleave_long = false;
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn stall(request: ControlRequest) -> Error {
+    Error::Usb(format!("stall (request {})", request.request))
+}
+
+/// 雑音用の擬似乱数（xorshift64*）
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let x = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        // 上位 53 ビットから (0, 1] の一様乱数
+        ((x >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 標準正規分布に従う独立な2つの値（Box-Muller 法）
+    fn gaussian_pair(&mut self) -> (f64, f64) {
+        let r = (-2.0 * self.next_f64().ln()).sqrt();
+        let theta = 2.0 * PI * self.next_f64();
+        (r * theta.cos(), r * theta.sin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hackrf::{block_on, HackRF, TRANSFER_BUFFER_SIZE};
+    use crate::{block_frequency, SweepAssembler, FFT};
+
+    fn blackman(n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| {
+                let x = i as f32 / n as f32;
+                0.42 - 0.5 * (2.0 * std::f32::consts::PI * x).cos() + 0.08 * (4.0 * std::f32::consts::PI * x).cos()
+            })
+            .collect()
+    }
+
+    /// worker.js と同じ設定で掃引を開始する
+    fn start_sweep(hackrf: &HackRF<SimulatedDevice>, low_mhz: u16, high_mhz: u16) {
+        block_on(async {
+            hackrf.set_sample_rate_manual(20_000_000, 1).await?;
+            hackrf.set_lna_gain(16).await?;
+            hackrf.set_vga_gain(20).await?;
+            let plan = SweepPlan::new(&[(low_mhz, high_mhz)], BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, SweepStyle::Interleaved)?;
+            hackrf.init_sweep(&plan).await?;
+            hackrf.start_rx_sweep().await
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sweep_headers() {
+        let hackrf = HackRF::new(SimulatedDevice::new(Scene::default()));
+        start_sweep(&hackrf, 2400, 2440);
+        let transfer = block_on(hackrf.read_transfer()).unwrap();
+        assert_eq!(transfer.len(), TRANSFER_BUFFER_SIZE);
+
+        let frequencies: Vec<u64> = transfer.chunks_exact(BYTES_PER_BLOCK).map(|b| block_frequency(b).unwrap()).collect();
+        // インターリーブ: +5 MHz, +15 MHz を繰り返し、2440 MHz に達したら先頭に戻る
+        let sweep = [2_400_000_000, 2_405_000_000, 2_420_000_000, 2_425_000_000];
+        for (i, &f) in frequencies.iter().enumerate() {
+            assert_eq!(f, sweep[i % 4], "block {}", i);
+        }
+    }
+
+    #[test]
+    fn test_linear_multi_range_sweep() {
+        let hackrf = HackRF::new(SimulatedDevice::new(Scene::default()));
+        block_on(async {
+            let plan = SweepPlan::new(&[(100, 140), (400, 420)], 2 * BYTES_PER_BLOCK as u32, 20_000_000, 0, SweepStyle::Linear)?;
+            hackrf.init_sweep(&plan).await?;
+            hackrf.start_rx_sweep().await
+        })
+        .unwrap();
+
+        let transfer = block_on(hackrf.read_transfer()).unwrap();
+        let frequencies: Vec<u64> = transfer.chunks_exact(BYTES_PER_BLOCK).map(|b| block_frequency(b).unwrap()).collect();
+        // 1ステップ 2 ブロック
+        let steps = [100_000_000, 120_000_000, 400_000_000];
+        for (i, &f) in frequencies.iter().enumerate() {
+            assert_eq!(f, steps[(i / 2) % 3], "block {}", i);
+        }
+    }
+
+    #[test]
+    fn test_control_requests() {
+        let device = SimulatedDevice::new(Scene::default());
+        let hackrf = HackRF::new(device);
+        block_on(async {
+            hackrf.set_sample_rate_manual(8_000_000, 2).await?;
+            hackrf.set_amp_enable(true).await?;
+            hackrf.set_lna_gain(24).await?;
+            hackrf.set_vga_gain(31).await?;
+            hackrf.set_freq(915_000_000).await
+        })
+        .unwrap();
+        let device = hackrf.transport();
+        assert_eq!(device.sample_rate(), 4e6);
+        assert_eq!(device.baseband_filter_bandwidth(), 2_500_000);
+        assert_eq!(device.total_gain_db(), 24.0 + 30.0 + AMP_GAIN_DEFAULT_DB);
+        assert_eq!(block_on(hackrf.board_id_read()), Ok(2));
+
+        // 掃引の設定前に掃引モードにはできない
+        assert!(block_on(hackrf.start_rx_sweep()).is_err());
+        assert!(block_on(hackrf.read_transfer()).is_err());
+    }
+
+    #[test]
+    fn test_receive_mode_tone() {
+        let scene = Scene {
+            carriers: vec![Carrier { frequency_hz: 100_250_000.0, level_db: -40.0 }],
+            noise_db: -200.0,
+            dc_offset: (0.0, 0.0),
+        };
+        let hackrf = HackRF::new(SimulatedDevice::new(scene));
+        block_on(async {
+            hackrf.set_sample_rate_manual(2_000_000, 1).await?;
+            hackrf.set_freq(100_000_000).await?;
+            hackrf.set_vga_gain(20).await?;
+            hackrf.start_rx().await
+        })
+        .unwrap();
+        let data = block_on(hackrf.read_transfer()).unwrap();
+
+        // +250 kHz / 2 MHz * 64 = +8 ビン。振幅は -40 dBFS + 20 dB = 0.1 * 127
+        let n = 64;
+        let mut fft = FFT::new(n, &vec![1.0; n]);
+        let mut result = vec![0.0f32; n];
+        fft.fft(as_i8(&data[..n * 2]), &mut result).unwrap();
+        let peak = (0..n).max_by(|&a, &b| result[a].total_cmp(&result[b])).unwrap();
+        assert_eq!(peak, n / 2 + 8);
+        assert!((result[peak] - 10.0 * (0.1f32 * 127.0 / 128.0).log10()).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_end_to_end_sweep() {
+        // 2400-2440 MHz の掃引で 2431.5 MHz の搬送波を見つける
+        let carrier = 2_431_500_000.0;
+        let scene = Scene {
+            carriers: vec![Carrier { frequency_hz: carrier, level_db: -50.0 }],
+            ..Scene::default()
+        };
+        let hackrf = HackRF::new(SimulatedDevice::new(scene));
+        start_sweep(&hackrf, 2400, 2440);
+
+        let n = 256;
+        let bin_count = 40 / 20 * n;
+        let mut fft = FFT::new(n, &blackman(n));
+        let mut sweep = SweepAssembler::new(2400e6, 2440e6, bin_count).unwrap();
+        let transfer = block_on(hackrf.read_transfer()).unwrap();
+        assert!(sweep.push_transfer(&mut fft, &transfer).unwrap() >= 2);
+
+        let line = sweep.completed();
+        let peak = (0..bin_count).max_by(|&a, &b| line[a].total_cmp(&line[b])).unwrap();
+        let expected = ((carrier - 2400e6) / 40e6 * bin_count as f64) as usize;
+        assert!(peak.abs_diff(expected) <= 1, "peak at {}, expected {}", peak, expected);
+
+        // 総ゲイン 36 dB で雑音は -34 dBFS、搬送波は -14 dBFS
+        let mut sorted = line.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = sorted[bin_count / 2];
+        assert!(line[peak] - median > 5.0, "peak {} dB, median {} dB", line[peak], median);
+    }
+
+    #[test]
+    fn test_end_to_end_multi_range_sweep() {
+        // 433.92, 868.3, 2440 MHz の搬送波を1回の掃引で見つける
+        let carriers = [433_920_000.0, 868_300_000.0, 2_440_000_000.0];
+        let scene = Scene {
+            carriers: carriers.iter().map(|&frequency_hz| Carrier { frequency_hz, level_db: -50.0 }).collect(),
+            ..Scene::default()
+        };
+        let hackrf = HackRF::new(SimulatedDevice::new(scene));
+        let plan = SweepPlan::new(&[(420, 440), (860, 880), (2430, 2450)], BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, SweepStyle::Interleaved)
+            .unwrap();
+        let rate = block_on(async {
+            let rate = hackrf.set_sample_rate(20e6).await?;
+            hackrf.set_lna_gain(16).await?;
+            hackrf.set_vga_gain(20).await?;
+            hackrf.init_sweep(&plan).await?;
+            hackrf.start_rx_sweep().await?;
+            Ok::<_, Error>(rate)
+        })
+        .unwrap();
+
+        let n = 256;
+        let mut fft = FFT::new(n, &blackman(n));
+        let mut sweep = SweepAssembler::from_plan(&plan, n, &rate).unwrap();
+        let transfer = block_on(hackrf.read_transfer()).unwrap();
+        // 1回の掃引は 3 範囲 × 2 ステップ = 6 ブロックなので、16 ブロック中の 0, 6, 12 番目で完了する
+        assert_eq!(sweep.push_transfer(&mut fft, &transfer).unwrap(), 3);
+
+        for (i, &carrier) in carriers.iter().enumerate() {
+            let segment = sweep.segments()[i];
+            let line = sweep.completed_segment(i);
+            let peak = (0..segment.bin_count).max_by(|&a, &b| line[a].total_cmp(&line[b])).unwrap();
+            let expected = ((carrier - segment.low_freq_hz) / segment.bin_width_hz) as usize;
+            assert!(peak.abs_diff(expected) <= 1, "range {}: peak at {}, expected {}", i, peak, expected);
+        }
+    }
+
+    #[test]
+    fn test_simulated_hackrf() {
+        // worker.js と同じ順序で操作し、2440 MHz の搬送波を見つける
+        let mut sim = SimulatedHackRF::new(-70.0);
+        sim.add_carrier(2_440_000_000.0, -50.0);
+        assert_eq!(sim.board_id_read(), Ok(2));
+        assert_eq!(sim.board_partid_serialno_read().unwrap().len(), 6);
+        sim.set_sample_rate_manual(20_000_000, 1).unwrap();
+        sim.set_lna_gain(16).unwrap();
+        sim.set_vga_gain(20).unwrap();
+        assert_eq!(sim.total_gain_db(), 36.0);
+
+        assert_eq!(sim.init_sweep(&[2400], BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, 1).err(),
+            Some(Error::InvalidParameter { name: "Range list length", value: 1.0 }));
+        assert_eq!(sim.init_sweep(&[2400, 2460], BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, 2).err(), Some(Error::InvalidSweepStyle(2)));
+        sim.init_sweep(&[2400, 2460], BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, SweepStyle::Interleaved as u8).unwrap();
+        sim.start_rx_sweep().unwrap();
+
+        let n = 256;
+        let bin_count = 60 / 20 * n;
+        let mut fft = FFT::new(n, &blackman(n));
+        let mut sweep = SweepAssembler::new(2400e6, 2460e6, bin_count).unwrap();
+        assert!(sweep.push_transfer(&mut fft, &sim.read_transfer().unwrap()).unwrap() >= 1);
+        let line = sweep.completed().to_vec();
+        let peak = (0..bin_count).max_by(|&a, &b| line[a].total_cmp(&line[b])).unwrap();
+        assert!(peak.abs_diff(2 * n) <= 1, "peak at {}", peak);
+
+        // 搬送波を取り除くとピークが消える
+        sim.clear_carriers();
+        assert!(sweep.push_transfer(&mut fft, &sim.read_transfer().unwrap()).unwrap() >= 1);
+        assert!(sweep.completed()[peak] < line[peak] - 10.0);
+
+        sim.stop_rx().unwrap();
+        assert!(sim.read_transfer().is_err());
+    }
+
+    fn as_i8(data: &[u8]) -> &[i8] {
+        unsafe { std::slice::from_raw_parts(data.as_ptr() as *const i8, data.len()) }
+    }
+}
@@ -0,0 +1,157 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use crate::Error;
+
+/// ベンダーリクエストのセットアップ（bmRequestType は vendor / device 固定）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlRequest {
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+}
+
+impl ControlRequest {
+    pub fn new(request: u8, value: u16, index: u16) -> Self {
+        ControlRequest { request, value, index }
+    }
+}
+
+/// HackRF との USB 通信路。
+///
+/// HackRF のプロトコルはベンダーリクエストのコントロール転送と、
+/// エンドポイント 1 からのバルク転送（受信）だけで構成される。
+/// WebUSB（`WebUsbTransport`）やテスト用のモック（`MockTransport`）がこれを実装する。
+///
+/// WebUSB の Promise は `Send` ではないため、返す Future にも `Send` を要求しない。
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    /// デバイスからホストへのコントロール転送。最大 `length` バイトを受け取る。
+    async fn control_in(&self, request: ControlRequest, length: u16) -> Result<Vec<u8>, Error>;
+
+    /// ホストからデバイスへのコントロール転送。
+    async fn control_out(&self, request: ControlRequest, data: &[u8]) -> Result<(), Error>;
+
+    /// バルク転送（受信）。最大 `length` バイトを受け取る。
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error>;
+
+    /// デバイスディスクリプタの bcdDevice（ファームウェアの USB API バージョン）
+    fn device_version(&self) -> u16;
+}
+
+/// `MockTransport` に記録された転送
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockCall {
+    ControlIn { request: ControlRequest, length: u16 },
+    ControlOut { request: ControlRequest, data: Vec<u8> },
+    BulkIn { endpoint: u8, length: usize },
+}
+
+/// テスト用のトランスポート。
+///
+/// 全ての転送を記録し、コントロール転送（IN）にはリクエスト番号ごとに設定した応答
+/// （未設定なら `length` バイトの 0）を、バルク転送には `push_bulk_in` で積んだデータを順に返す。
+/// Future は常に即座に完了する。
+#[derive(Debug)]
+pub struct MockTransport {
+    device_version: u16,
+    calls: RefCell<Vec<MockCall>>,
+    control_in_responses: RefCell<HashMap<u8, Vec<u8>>>,
+    bulk_in_data: RefCell<VecDeque<Vec<u8>>>,
+    failing_requests: RefCell<Vec<u8>>,
+}
+
+impl MockTransport {
+    /// `device_version` は bcdDevice（例: 0x0107）
+    pub fn new(device_version: u16) -> Self {
+        MockTransport {
+            device_version,
+            calls: RefCell::new(Vec::new()),
+            control_in_responses: RefCell::new(HashMap::new()),
+            bulk_in_data: RefCell::new(VecDeque::new()),
+            failing_requests: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// リクエスト番号 `request` のコントロール転送（IN）に返すデータを設定する。
+    pub fn set_control_in_response(&self, request: u8, data: Vec<u8>) {
+        self.control_in_responses.borrow_mut().insert(request, data);
+    }
+
+    /// 次のバルク転送で返すデータを積む。
+    pub fn push_bulk_in(&self, data: Vec<u8>) {
+        self.bulk_in_data.borrow_mut().push_back(data);
+    }
+
+    /// リクエスト番号 `request` のコントロール転送を失敗させる。
+    pub fn fail_request(&self, request: u8) {
+        self.failing_requests.borrow_mut().push(request);
+    }
+
+    /// これまでの転送の記録
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// 記録を消去する。
+    pub fn clear_calls(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    fn check(&self, request: ControlRequest) -> Result<(), Error> {
+        if self.failing_requests.borrow().contains(&request.request) {
+            return Err(Error::Usb(format!("stall (request {})", request.request)));
+        }
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    async fn control_in(&self, request: ControlRequest, length: u16) -> Result<Vec<u8>, Error> {
+        self.calls.borrow_mut().push(MockCall::ControlIn { request, length });
+        self.check(request)?;
+        let mut data = self
+            .control_in_responses
+            .borrow()
+            .get(&request.request)
+            .cloned()
+            .unwrap_or_else(|| vec![0; length as usize]);
+        data.truncate(length as usize);
+        Ok(data)
+    }
+
+    async fn control_out(&self, request: ControlRequest, data: &[u8]) -> Result<(), Error> {
+        self.calls.borrow_mut().push(MockCall::ControlOut { request, data: data.to_vec() });
+        self.check(request)
+    }
+
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        self.calls.borrow_mut().push(MockCall::BulkIn { endpoint, length });
+        let mut data = self
+            .bulk_in_data
+            .borrow_mut()
+            .pop_front()
+            .ok_or_else(|| Error::Usb("no bulk data queued".to_string()))?;
+        data.truncate(length);
+        Ok(data)
+    }
+
+    fn device_version(&self) -> u16 {
+        self.device_version
+    }
+}
+
+/// 即座に完了する Future を実行する（テストと `SimulatedHackRF` 用）。
+///
+/// `MockTransport` と `SimulatedDevice` の Future は待機しないので、何もしない Waker でポーリングするだけでよい。
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use js_sys::Uint8Array;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{UsbControlTransferParameters, UsbDevice, UsbInTransferResult, UsbRecipient, UsbRequestType, UsbTransferStatus};
+
+use super::{ControlRequest, Transport, USB_CONFIG_STANDARD};
+use crate::Error;
+
+/// WebUSB の `USBDevice` を使うトランスポート。
+///
+/// web-sys の WebUSB は unstable API のため `--cfg=web_sys_unstable_apis` が必要
+/// （`.cargo/config.toml` で wasm32 ターゲットに設定している）。
+#[derive(Clone, Debug)]
+pub struct WebUsbTransport {
+    device: UsbDevice,
+}
+
+impl WebUsbTransport {
+    /// すでに開かれたデバイスを使う。
+    pub fn new(device: UsbDevice) -> Self {
+        WebUsbTransport { device }
+    }
+
+    /// デバイスを開き、構成とインターフェースを選択する（hackrf.js の `open` と同じ）。
+    pub async fn open(device: UsbDevice) -> Result<Self, Error> {
+        JsFuture::from(device.open()).await.map_err(js_error)?;
+        JsFuture::from(device.select_configuration(USB_CONFIG_STANDARD)).await.map_err(js_error)?;
+        JsFuture::from(device.claim_interface(0)).await.map_err(js_error)?;
+        Ok(WebUsbTransport { device })
+    }
+
+    pub async fn close(&self) -> Result<(), Error> {
+        JsFuture::from(self.device.close()).await.map_err(js_error)?;
+        Ok(())
+    }
+
+    pub fn device(&self) -> &UsbDevice {
+        &self.device
+    }
+}
+
+fn setup(request: ControlRequest) -> UsbControlTransferParameters {
+    UsbControlTransferParameters::new(request.index, UsbRecipient::Device, request.request, UsbRequestType::Vendor, request.value)
+}
+
+fn js_error(e: JsValue) -> Error {
+    Error::Usb(e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+}
+
+/// IN 転送の結果からデータを取り出す。
+fn in_transfer_data(result: UsbInTransferResult) -> Result<Vec<u8>, Error> {
+    let status = result.status();
+    if status != UsbTransferStatus::Ok {
+        return Err(Error::Usb(format!("{:?}", status)));
+    }
+    Ok(match result.data() {
+        Some(view) => {
+            Uint8Array::new_with_byte_offset_and_length(&view.buffer(), view.byte_offset() as u32, view.byte_length() as u32)
+                .to_vec()
+        }
+        None => Vec::new(),
+    })
+}
+
+impl Transport for WebUsbTransport {
+    async fn control_in(&self, request: ControlRequest, length: u16) -> Result<Vec<u8>, Error> {
+        let promise = self.device.control_transfer_in(&setup(request), length);
+        in_transfer_data(JsFuture::from(promise).await.map_err(js_error)?)
+    }
+
+    async fn control_out(&self, request: ControlRequest, data: &[u8]) -> Result<(), Error> {
+        let promise = if data.is_empty() {
+            self.device.control_transfer_out(&setup(request))
+        } else {
+            self.device.control_transfer_out_with_u8_slice(&setup(request), data).map_err(js_error)?
+        };
+        let result = JsFuture::from(promise).await.map_err(js_error)?;
+        let status = result.status();
+        if status != UsbTransferStatus::Ok {
+            return Err(Error::Usb(format!("{:?}", status)));
+        }
+        Ok(())
+    }
+
+    async fn bulk_in(&self, endpoint: u8, length: usize) -> Result<Vec<u8>, Error> {
+        let promise = self.device.transfer_in(endpoint, length as u32);
+        in_transfer_data(JsFuture::from(promise).await.map_err(js_error)?)
+    }
+
+    fn device_version(&self) -> u16 {
+        ((self.device.device_version_major() as u16) << 8)
+            | ((self.device.device_version_minor() as u16) << 4)
+            | self.device.device_version_subminor() as u16
+    }
+}
@@ -0,0 +1,267 @@
+//! `FFT::fft` の前処理（i8 → f32 変換と窓関数）と後処理（振幅・スムージング・dB 変換）のループ。
+//!
+//! `scalar` は参照実装で、`simd` は `simd` フィーチャ有効時に使われる高速版。
+//! どちらを使うかはビルド時に決まり、`simd` は参照実装との差分テストのため常にコンパイルされる。
+
+use rustfft::num_complex::Complex;
+
+/// 参照実装。`f32::log10` を使う。
+#[cfg_attr(feature = "simd", allow(dead_code))]
+pub(crate) mod scalar {
+    use super::*;
+
+    /// `out[i] = input[i] * window[i]`（i8 → f32 変換込み）
+    pub(crate) fn preprocess(input: &[Complex<i8>], window: &[f32], out: &mut [Complex<f32>]) {
+        for ((o, x), &w) in out.iter_mut().zip(input.iter()).zip(window.iter()) {
+            *o = Complex {
+                re: x.re as f32,
+                im: x.im as f32,
+            } * w;
+        }
+    }
+
+    /// 振幅 → 指数移動平均（`alpha > 0` のとき）→ dB 変換
+    pub(crate) fn postprocess(bins: &[Complex<f32>], prev: &mut [f32], alpha: f32, out: &mut [f32]) {
+        let inv_alpha = 1.0 - alpha;
+        for ((o, bin), p) in out.iter_mut().zip(bins.iter()).zip(prev.iter_mut()) {
+            // すでに scaled_window により 1/n 倍されているため、norm() するだけでよい
+            let magnitude = bin.norm();
+
+            let smoothed = if alpha > 0.0 {
+                let s = alpha * *p + inv_alpha * magnitude;
+                *p = s;
+                s
+            } else {
+                magnitude
+            };
+
+            // log10(0) = -inf を避けるため、小さな値で下限を設ける
+            *o = smoothed.max(1e-10).log10() * 10.0;
+        }
+    }
+}
+
+/// 高速版。wasm32 で `simd128` が有効な場合は SIMD 命令を直接使い、
+/// それ以外では自動ベクトル化しやすい形のループと近似 log10 を使う。
+#[cfg_attr(not(feature = "simd"), allow(dead_code))]
+pub(crate) mod simd {
+    use super::*;
+
+    const SQRT_2: f32 = std::f32::consts::SQRT_2;
+    const LN_2: f32 = std::f32::consts::LN_2;
+    const LOG10_E: f32 = std::f32::consts::LOG10_E;
+
+    /// 正の正規化数に対する近似 log10。
+    ///
+    /// `x = m * 2^e`（`m` は `[√2/2, √2)`）に分解し、
+    /// `ln(m) = 2 atanh(t)`, `t = (m - 1) / (m + 1)` を t^7 の項まで展開する。
+    /// `|t| < 0.172` なので打ち切り誤差は 1e-7 程度。
+    #[inline]
+    pub(crate) fn fast_log10(x: f32) -> f32 {
+        let bits = x.to_bits();
+        let mut e = ((bits >> 23) & 0xff) as i32 - 127;
+        let mut m = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000);
+        if m > SQRT_2 {
+            m *= 0.5;
+            e += 1;
+        }
+        let t = (m - 1.0) / (m + 1.0);
+        let t2 = t * t;
+        let ln_m = t * (2.0 + t2 * (2.0 / 3.0 + t2 * (2.0 / 5.0 + t2 * (2.0 / 7.0))));
+        (e as f32 * LN_2 + ln_m) * LOG10_E
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+    pub(crate) fn preprocess(input: &[Complex<i8>], window: &[f32], out: &mut [Complex<f32>]) {
+        // 分岐のない単純なループにして自動ベクトル化させる
+        for ((o, x), &w) in out.iter_mut().zip(input.iter()).zip(window.iter()) {
+            o.re = x.re as f32 * w;
+            o.im = x.im as f32 * w;
+        }
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+    pub(crate) fn postprocess(bins: &[Complex<f32>], prev: &mut [f32], alpha: f32, out: &mut [f32]) {
+        let inv_alpha = 1.0 - alpha;
+        if alpha > 0.0 {
+            for ((o, bin), p) in out.iter_mut().zip(bins.iter()).zip(prev.iter_mut()) {
+                let s = alpha * *p + inv_alpha * bin.norm_sqr().sqrt();
+                *p = s;
+                *o = fast_log10(s.max(1e-10)) * 10.0;
+            }
+        } else {
+            for (o, bin) in out.iter_mut().zip(bins.iter()) {
+                *o = fast_log10(bin.norm_sqr().sqrt().max(1e-10)) * 10.0;
+            }
+        }
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    pub(crate) use self::wasm::{postprocess, preprocess};
+
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    mod wasm {
+        use super::*;
+        use core::arch::wasm32::*;
+
+        /// `Complex<f32>` のスライスを `[re0, im0, re1, im1, ...]` として扱う
+        fn as_f32(bins: &[Complex<f32>]) -> &[f32] {
+            unsafe { std::slice::from_raw_parts(bins.as_ptr() as *const f32, bins.len() * 2) }
+        }
+
+        fn as_f32_mut(bins: &mut [Complex<f32>]) -> &mut [f32] {
+            unsafe { std::slice::from_raw_parts_mut(bins.as_mut_ptr() as *mut f32, bins.len() * 2) }
+        }
+
+        pub(crate) fn preprocess(input: &[Complex<i8>], window: &[f32], out: &mut [Complex<f32>]) {
+            let len = input.len().min(window.len()).min(out.len());
+            let raw = unsafe { std::slice::from_raw_parts(input.as_ptr() as *const i8, len * 2) };
+            let dst = as_f32_mut(&mut out[..len]);
+
+            // 1回に 8 サンプル（i8 16個）を処理する
+            let chunks = len / 8;
+            for c in 0..chunks {
+                unsafe {
+                    let v = v128_load(raw.as_ptr().add(c * 16) as *const v128);
+                    let lo = i16x8_extend_low_i8x16(v);
+                    let hi = i16x8_extend_high_i8x16(v);
+                    let x0 = f32x4_convert_i32x4(i32x4_extend_low_i16x8(lo));
+                    let x1 = f32x4_convert_i32x4(i32x4_extend_high_i16x8(lo));
+                    let x2 = f32x4_convert_i32x4(i32x4_extend_low_i16x8(hi));
+                    let x3 = f32x4_convert_i32x4(i32x4_extend_high_i16x8(hi));
+
+                    // 窓は1サンプルにつき re, im の2要素へ複製する
+                    let w0 = v128_load(window.as_ptr().add(c * 8) as *const v128);
+                    let w1 = v128_load(window.as_ptr().add(c * 8 + 4) as *const v128);
+                    let p = dst.as_mut_ptr().add(c * 16);
+                    v128_store(p as *mut v128, f32x4_mul(x0, i32x4_shuffle::<0, 0, 1, 1>(w0, w0)));
+                    v128_store(p.add(4) as *mut v128, f32x4_mul(x1, i32x4_shuffle::<2, 2, 3, 3>(w0, w0)));
+                    v128_store(p.add(8) as *mut v128, f32x4_mul(x2, i32x4_shuffle::<0, 0, 1, 1>(w1, w1)));
+                    v128_store(p.add(12) as *mut v128, f32x4_mul(x3, i32x4_shuffle::<2, 2, 3, 3>(w1, w1)));
+                }
+            }
+            for i in chunks * 8..len {
+                dst[i * 2] = raw[i * 2] as f32 * window[i];
+                dst[i * 2 + 1] = raw[i * 2 + 1] as f32 * window[i];
+            }
+        }
+
+        /// `fast_log10` の4要素版
+        #[inline]
+        fn fast_log10x4(x: v128) -> v128 {
+            let e = i32x4_sub(u32x4_shr(x, 23), i32x4_splat(127));
+            let m = v128_or(v128_and(x, i32x4_splat(0x007f_ffff)), i32x4_splat(0x3f80_0000));
+            let large = f32x4_gt(m, f32x4_splat(SQRT_2));
+            let m = v128_bitselect(f32x4_mul(m, f32x4_splat(0.5)), m, large);
+            let e = v128_bitselect(i32x4_add(e, i32x4_splat(1)), e, large);
+
+            let one = f32x4_splat(1.0);
+            let t = f32x4_div(f32x4_sub(m, one), f32x4_add(m, one));
+            let t2 = f32x4_mul(t, t);
+            let mut poly = f32x4_splat(2.0 / 7.0);
+            poly = f32x4_add(f32x4_splat(2.0 / 5.0), f32x4_mul(t2, poly));
+            poly = f32x4_add(f32x4_splat(2.0 / 3.0), f32x4_mul(t2, poly));
+            poly = f32x4_add(f32x4_splat(2.0), f32x4_mul(t2, poly));
+            let ln = f32x4_add(f32x4_mul(f32x4_convert_i32x4(e), f32x4_splat(LN_2)), f32x4_mul(t, poly));
+            f32x4_mul(ln, f32x4_splat(LOG10_E))
+        }
+
+        pub(crate) fn postprocess(bins: &[Complex<f32>], prev: &mut [f32], alpha: f32, out: &mut [f32]) {
+            let len = bins.len().min(prev.len()).min(out.len());
+            let src = as_f32(&bins[..len]);
+            let inv_alpha = 1.0 - alpha;
+            let floor = f32x4_splat(1e-10);
+            let ten = f32x4_splat(10.0);
+
+            // 1回に 4 ビンを処理する
+            let chunks = len / 4;
+            for c in 0..chunks {
+                unsafe {
+                    let a = v128_load(src.as_ptr().add(c * 8) as *const v128);
+                    let b = v128_load(src.as_ptr().add(c * 8 + 4) as *const v128);
+                    let a2 = f32x4_mul(a, a);
+                    let b2 = f32x4_mul(b, b);
+                    let power = f32x4_add(i32x4_shuffle::<0, 2, 4, 6>(a2, b2), i32x4_shuffle::<1, 3, 5, 7>(a2, b2));
+                    let mut s = f32x4_sqrt(power);
+                    if alpha > 0.0 {
+                        let p = prev.as_mut_ptr().add(c * 4) as *mut v128;
+                        s = f32x4_add(f32x4_mul(f32x4_splat(alpha), v128_load(p)), f32x4_mul(f32x4_splat(inv_alpha), s));
+                        v128_store(p, s);
+                    }
+                    let db = f32x4_mul(fast_log10x4(f32x4_max(s, floor)), ten);
+                    v128_store(out.as_mut_ptr().add(c * 4) as *mut v128, db);
+                }
+            }
+            for i in chunks * 4..len {
+                let mut s = bins[i].norm_sqr().sqrt();
+                if alpha > 0.0 {
+                    s = alpha * prev[i] + inv_alpha * s;
+                    prev[i] = s;
+                }
+                out[i] = fast_log10(s.max(1e-10)) * 10.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // simd128 なしでは wasm でも代替実装どうしの比較になってしまう
+    #[cfg(all(target_arch = "wasm32", feature = "simd", not(target_feature = "simd128")))]
+    compile_error!("wasm tests with the simd feature need RUSTFLAGS=\"-C target-feature=+simd128\" (cargo make test-wasm-simd)");
+
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    fn test_fast_log10_accuracy() {
+        let mut x = 1e-10f32;
+        while x < 1e3 {
+            let error = (simd::fast_log10(x) - x.log10()).abs();
+            assert!(error < 1e-5, "log10({}) error {}", x, error);
+            x *= 1.0137;
+        }
+        assert_eq!(simd::fast_log10(1.0), 0.0);
+    }
+
+    /// 窓関数・入力・スムージングを変えて、高速版と参照実装の出力を比較する。
+    ///
+    /// wasm32 では `wasm-pack test --node` で実行する（`cargo make test-wasm-simd` なら simd128 版を比較する）
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    fn test_simd_matches_scalar() {
+        for &n in &[1usize, 7, 8, 33, 1024] {
+            let input: Vec<Complex<i8>> = (0..n)
+                .map(|i| Complex::new((i as i8).wrapping_mul(37), (i as i8).wrapping_mul(-11).wrapping_add(3)))
+                .collect();
+            let window: Vec<f32> = (0..n).map(|i| (0.3 + (i as f32 * 0.1).sin().abs()) / (128.0 * n as f32)).collect();
+
+            let mut expected = vec![Complex::new(0.0f32, 0.0); n];
+            let mut actual = vec![Complex::new(0.0f32, 0.0); n];
+            scalar::preprocess(&input, &window, &mut expected);
+            simd::preprocess(&input, &window, &mut actual);
+            assert_eq!(actual, expected, "preprocess n = {}", n);
+
+            // 振幅にゼロ（下限値でクリップされる）を含める
+            expected[0] = Complex::new(0.0, 0.0);
+            for &alpha in &[0.0f32, 0.5] {
+                let mut prev_expected = vec![0.01f32; n];
+                let mut prev_actual = prev_expected.clone();
+                let mut out_expected = vec![0.0f32; n];
+                let mut out_actual = vec![0.0f32; n];
+                for _ in 0..3 {
+                    scalar::postprocess(&expected, &mut prev_expected, alpha, &mut out_expected);
+                    simd::postprocess(&expected, &mut prev_actual, alpha, &mut out_actual);
+                }
+                for i in 0..n {
+                    assert!(
+                        (out_actual[i] - out_expected[i]).abs() < 1e-3,
+                        "postprocess n = {}, alpha = {}, index {}: simd={}, scalar={}",
+                        n, alpha, i, out_actual[i], out_expected[i]
+                    );
+                    assert!((prev_actual[i] - prev_expected[i]).abs() <= prev_expected[i] * 1e-5);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, SweepAssembler};
+
+/// リミットラインの折れ点
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LimitPoint {
+    pub frequency_hz: f64,
+    pub level_db: f32,
+}
+
+/// 上限ラインか下限ラインか
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitKind {
+    Upper,
+    Lower,
+}
+
+/// リミットラインの1区間（隣り合う折れ点の間）のマージン
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SegmentMargin {
+    pub kind: LimitKind,
+    /// ライン内の区間番号（折れ点 `segment` と `segment + 1` の間）
+    pub segment: usize,
+    pub start_hz: f64,
+    pub stop_hz: f64,
+    /// 区間内で最も小さいマージン [dB]（負なら違反）。区間内にデータのあるビンがなければ NaN
+    pub margin_db: f32,
+    /// `margin_db` となった周波数 [Hz]
+    pub worst_frequency_hz: f64,
+}
+
+/// リミットを超えたビン
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Violation {
+    pub frequency_hz: f64,
+    pub level_db: f32,
+    /// そのビンで最も小さいマージン [dB]（負）
+    pub margin_db: f32,
+    pub kind: LimitKind,
+}
+
+/// 上限・下限のリミットライン（周波数 / dB の折れ線）によるマスク試験。
+///
+/// 各ビンについて、ビンの周波数を含む区間の線形補間値と比べる。上限ラインはライン − 値、
+/// 下限ラインは値 − ラインをマージンとし、負のマージンがあれば不合格とする。
+/// 同じ周波数の折れ点を2つ置くと段差になり、その周波数では厳しいほうの区間で判定される。
+/// ラインの範囲外のビンと NaN（データなし）のビンは試験しない。
+/// 折れ点は1点ずつ追加できるが、1点だけのラインがあるマスクは試験も JSON への書き出しもできない。
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LimitMask {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    upper: Vec<LimitPoint>,
+    #[serde(default)]
+    lower: Vec<LimitPoint>,
+}
+
+#[wasm_bindgen]
+impl LimitMask {
+    /// ラインのない空のマスクを作成する。
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: &str) -> LimitMask {
+        LimitMask { name: name.to_string(), upper: Vec::new(), lower: Vec::new() }
+    }
+
+    /// JSON からマスクを読み込む。
+    ///
+    /// 形式は `{"name": ..., "upper": [{"frequency_hz": ..., "level_db": ...}, ...], "lower": [...]}`。
+    /// `upper` / `lower` は省略できる。
+    ///
+    /// # エラー
+    /// * `Error::Json` - JSON として解釈できない場合
+    /// * `Error::InvalidLimitLine` - 折れ点が周波数順でない、有限でない、または1点だけの場合
+    pub fn from_json(json: &str) -> Result<LimitMask, Error> {
+        let mask: LimitMask = serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))?;
+        mask.validate()?;
+        Ok(mask)
+    }
+
+    /// マスクを JSON に書き出す（`from_json` で読み込める形式）。
+    ///
+    /// # エラー
+    /// * `Error::InvalidLimitLine` - 1点だけのラインがある場合
+    pub fn to_json(&self) -> Result<String, Error> {
+        self.validate()?;
+        Ok(serde_json::to_string(self).expect("LimitMask is always serializable"))
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// 上限ラインの末尾に折れ点を追加する。
+    ///
+    /// # エラー
+    /// * `Error::InvalidLimitLine` - 周波数が直前の折れ点より小さい、または値が有限でない場合
+    pub fn add_upper_point(&mut self, frequency_hz: f64, level_db: f32) -> Result<(), Error> {
+        push_point(&mut self.upper, LimitPoint { frequency_hz, level_db }, "upper")
+    }
+
+    /// 下限ラインの末尾に折れ点を追加する。
+    ///
+    /// # エラー
+    /// * `Error::InvalidLimitLine` - 周波数が直前の折れ点より小さい、または値が有限でない場合
+    pub fn add_lower_point(&mut self, frequency_hz: f64, level_db: f32) -> Result<(), Error> {
+        push_point(&mut self.lower, LimitPoint { frequency_hz, level_db }, "lower")
+    }
+
+    /// ビンの周波数 `frequencies_hz` と値 `trace` を試験する。
+    ///
+    /// # エラー
+    /// * `Error::InvalidLimitLine` - 1点だけのラインがある場合
+    /// * `Error::InputLengthMismatch` - `frequencies_hz.len() != trace.len()` の場合
+    pub fn evaluate(&self, frequencies_hz: &[f64], trace: &[f32]) -> Result<MaskResult, Error> {
+        self.validate()?;
+        if frequencies_hz.len() != trace.len() {
+            return Err(Error::InputLengthMismatch { expected: frequencies_hz.len(), actual: trace.len() });
+        }
+
+        let mut segments: Vec<SegmentMargin> = [(LimitKind::Upper, &self.upper), (LimitKind::Lower, &self.lower)]
+            .into_iter()
+            .flat_map(|(kind, line)| {
+                line.windows(2).enumerate().map(move |(segment, p)| SegmentMargin {
+                    kind,
+                    segment,
+                    start_hz: p[0].frequency_hz,
+                    stop_hz: p[1].frequency_hz,
+                    margin_db: f32::NAN,
+                    worst_frequency_hz: f64::NAN,
+                })
+            })
+            .collect();
+        let upper_segments = self.upper.len().saturating_sub(1);
+
+        let mut violations = Vec::new();
+        for (&frequency, &level) in frequencies_hz.iter().zip(trace) {
+            if level.is_nan() {
+                continue;
+            }
+            let mut worst: Option<(f32, LimitKind)> = None;
+            for (i, segment) in segments.iter_mut().enumerate() {
+                if segment.start_hz >= segment.stop_hz || frequency < segment.start_hz || frequency > segment.stop_hz {
+                    continue;
+                }
+                let line = if i < upper_segments { &self.upper } else { &self.lower };
+                let limit = interpolate(&line[segment.segment], &line[segment.segment + 1], frequency);
+                let margin = match segment.kind {
+                    LimitKind::Upper => limit - level,
+                    LimitKind::Lower => level - limit,
+                };
+                if segment.margin_db.is_nan() || margin < segment.margin_db {
+                    segment.margin_db = margin;
+                    segment.worst_frequency_hz = frequency;
+                }
+                if worst.is_none_or(|(m, _)| margin < m) {
+                    worst = Some((margin, segment.kind));
+                }
+            }
+            if let Some((margin_db, kind)) = worst.filter(|&(m, _)| m < 0.0) {
+                violations.push(Violation { frequency_hz: frequency, level_db: level, margin_db, kind });
+            }
+        }
+
+        Ok(MaskResult { segments, violations })
+    }
+
+    /// 直前に完成した掃引フレームを試験する。
+    pub fn evaluate_sweep(&self, sweep: &SweepAssembler) -> Result<MaskResult, Error> {
+        let mut frequencies = vec![0.0; sweep.bin_count()];
+        sweep.frequencies(&mut frequencies)?;
+        self.evaluate(&frequencies, sweep.completed())
+    }
+}
+
+impl LimitMask {
+    pub fn upper(&self) -> &[LimitPoint] {
+        &self.upper
+    }
+
+    pub fn lower(&self) -> &[LimitPoint] {
+        &self.lower
+    }
+}
+
+/// マスク試験の結果
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MaskResult {
+    segments: Vec<SegmentMargin>,
+    violations: Vec<Violation>,
+}
+
+#[wasm_bindgen]
+impl MaskResult {
+    /// 違反がなければ合格
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// 全区間で最も小さいマージン [dB]。試験したビンがなければ NaN
+    pub fn worst_margin_db(&self) -> f32 {
+        self.segments.iter().map(|s| s.margin_db).filter(|m| !m.is_nan()).reduce(f32::min).unwrap_or(f32::NAN)
+    }
+
+    pub fn violation_count(&self) -> usize {
+        self.violations.len()
+    }
+
+    /// 違反したビンの周波数 [Hz]
+    pub fn violation_frequencies(&self) -> Vec<f64> {
+        self.violations.iter().map(|v| v.frequency_hz).collect()
+    }
+
+    /// 区間ごとのマージン [dB]（上限ラインの区間、下限ラインの区間の順）
+    pub fn segment_margins(&self) -> Vec<f32> {
+        self.segments.iter().map(|s| s.margin_db).collect()
+    }
+
+    /// 結果全体の JSON（`passed`、`segments`、`violations`）
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            passed: bool,
+            segments: &'a [SegmentMargin],
+            violations: &'a [Violation],
+        }
+        let report = Report { passed: self.passed(), segments: &self.segments, violations: &self.violations };
+        // NaN のマージンは null になる
+        serde_json::to_string(&report).expect("MaskResult is always serializable")
+    }
+}
+
+impl LimitMask {
+    /// `from_json` と同じ規則でラインを確かめる
+    fn validate(&self) -> Result<(), Error> {
+        validate_line(&self.upper, "upper")?;
+        validate_line(&self.lower, "lower")
+    }
+}
+
+impl MaskResult {
+    pub fn segments(&self) -> &[SegmentMargin] {
+        &self.segments
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+fn interpolate(a: &LimitPoint, b: &LimitPoint, frequency_hz: f64) -> f32 {
+    let t = (frequency_hz - a.frequency_hz) / (b.frequency_hz - a.frequency_hz);
+    a.level_db + (b.level_db - a.level_db) * t as f32
+}
+
+fn push_point(line: &mut Vec<LimitPoint>, point: LimitPoint, name: &str) -> Result<(), Error> {
+    if !point.frequency_hz.is_finite() || !point.level_db.is_finite() {
+        return Err(Error::InvalidLimitLine(format!("{} line has a non-finite point", name)));
+    }
+    if line.last().is_some_and(|last| point.frequency_hz < last.frequency_hz) {
+        return Err(Error::InvalidLimitLine(format!("{} line frequencies must be in ascending order", name)));
+    }
+    line.push(point);
+    Ok(())
+}
+
+fn validate_line(points: &[LimitPoint], name: &str) -> Result<(), Error> {
+    if points.len() == 1 {
+        return Err(Error::InvalidLimitLine(format!("{} line needs at least 2 points", name)));
+    }
+    let mut line = Vec::with_capacity(points.len());
+    for &point in points {
+        push_point(&mut line, point, name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 MHz まで -40 dB、100..200 MHz で -40 → -60 dB、200 MHz で -30 dB に段差
+    fn mask() -> LimitMask {
+        LimitMask::from_json(
+            r#"{
+                "name": "test",
+                "upper": [
+                    {"frequency_hz": 0, "level_db": -40},
+                    {"frequency_hz": 100e6, "level_db": -40},
+                    {"frequency_hz": 200e6, "level_db": -60},
+                    {"frequency_hz": 200e6, "level_db": -30},
+                    {"frequency_hz": 300e6, "level_db": -30}
+                ],
+                "lower": [
+                    {"frequency_hz": 0, "level_db": -100},
+                    {"frequency_hz": 300e6, "level_db": -100}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pass_fail() {
+        let mask = mask();
+        let frequencies = [50e6, 150e6, 200e6, 250e6, 350e6];
+
+        let result = mask.evaluate(&frequencies, &[-45.0, -55.0, -65.0, -35.0, 0.0]).unwrap();
+        assert!(result.passed());
+        // 150 MHz の上限は -50 dB なのでマージン 5 dB、範囲外の 350 MHz は試験しない
+        assert_eq!(result.worst_margin_db(), 5.0);
+        // 上限 4 区間（段差の区間は幅 0 なので試験しない）、下限 1 区間
+        let margins = result.segment_margins();
+        assert_eq!(margins.len(), 5);
+        assert_eq!(&margins[..2], &[5.0, 5.0]);
+        assert!(margins[2].is_nan());
+        assert_eq!(margins[3], 5.0);
+        assert_eq!(margins[4], 35.0);
+
+        // 200 MHz は段差の両側の区間に含まれ、厳しい -60 dB で判定される
+        let result = mask.evaluate(&frequencies, &[-45.0, -45.0, -50.0, -120.0, f32::NAN]).unwrap();
+        assert!(!result.passed());
+        assert_eq!(result.violation_frequencies(), vec![150e6, 200e6, 250e6]);
+        assert_eq!(
+            result.violations()[0],
+            Violation { frequency_hz: 150e6, level_db: -45.0, margin_db: -5.0, kind: LimitKind::Upper }
+        );
+        assert_eq!(result.violations()[1].margin_db, -10.0);
+        assert_eq!(result.violations()[2].kind, LimitKind::Lower);
+        assert_eq!(result.segments()[1].worst_frequency_hz, 200e6);
+        assert_eq!(result.worst_margin_db(), -20.0);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mask = mask();
+        assert_eq!(LimitMask::from_json(&mask.to_json().unwrap()).unwrap(), mask);
+
+        let mut built = LimitMask::new("built");
+        built.add_upper_point(0.0, -40.0).unwrap();
+        built.add_upper_point(1e6, -40.0).unwrap();
+        let json = built.to_json().unwrap();
+        assert_eq!(json, r#"{"name":"built","upper":[{"frequency_hz":0.0,"level_db":-40.0},{"frequency_hz":1000000.0,"level_db":-40.0}],"lower":[]}"#);
+        // 省略したラインは空
+        let mask = LimitMask::from_json(r#"{"upper": [{"frequency_hz": 0, "level_db": 0}, {"frequency_hz": 1, "level_db": 0}]}"#).unwrap();
+        assert!(mask.lower().is_empty());
+        assert_eq!(mask.name(), "");
+
+        let result = mask.evaluate(&[0.5], &[1.0]).unwrap();
+        assert_eq!(
+            result.to_json(),
+            r#"{"passed":false,"segments":[{"kind":"upper","segment":0,"start_hz":0.0,"stop_hz":1.0,"margin_db":-1.0,"worst_frequency_hz":0.5}],"violations":[{"frequency_hz":0.5,"level_db":1.0,"margin_db":-1.0,"kind":"upper"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_evaluate_sweep() {
+        let mut mask = LimitMask::new("flat");
+        mask.add_upper_point(100e6, -30.0).unwrap();
+        mask.add_upper_point(140e6, -30.0).unwrap();
+
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 16).unwrap();
+        let mut spectrum = [-50.0f32; 8];
+        spectrum[2] = -10.0;
+        sweep.place(100_000_000, &[-50.0; 8]);
+        sweep.place(120_000_000, &spectrum);
+        sweep.place(100_000_000, &[-50.0; 8]);
+
+        let result = mask.evaluate_sweep(&sweep).unwrap();
+        assert!(!result.passed());
+        // ビン 9（120 MHz + 2.5 MHz）
+        assert_eq!(result.violation_frequencies(), vec![122.5e6]);
+    }
+
+    #[test]
+    fn test_invalid_masks() {
+        assert!(matches!(LimitMask::from_json("{"), Err(Error::Json(_))));
+        assert!(matches!(
+            LimitMask::from_json(r#"{"upper": [{"frequency_hz": 0, "level_db": 0}]}"#),
+            Err(Error::InvalidLimitLine(_))
+        ));
+        assert!(matches!(
+            LimitMask::from_json(r#"{"lower": [{"frequency_hz": 2, "level_db": 0}, {"frequency_hz": 1, "level_db": 0}]}"#),
+            Err(Error::InvalidLimitLine(_))
+        ));
+        let mut mask = LimitMask::new("");
+        assert!(matches!(mask.add_lower_point(f64::NAN, 0.0), Err(Error::InvalidLimitLine(_))));
+        assert_eq!(mask.evaluate(&[1.0], &[]), Err(Error::InputLengthMismatch { expected: 1, actual: 0 }));
+
+        // 1点だけのラインは、追加の途中では持てるが試験も書き出しもできない（from_json と同じ規則）
+        mask.add_upper_point(0.0, -40.0).unwrap();
+        let one_point = Error::InvalidLimitLine("upper line needs at least 2 points".to_string());
+        assert_eq!(mask.evaluate(&[0.0], &[-50.0]).err(), Some(one_point.clone()));
+        assert_eq!(mask.to_json().err(), Some(one_point));
+        mask.add_upper_point(1e6, -40.0).unwrap();
+        assert!(mask.evaluate(&[0.0], &[-50.0]).unwrap().passed());
+        assert!(LimitMask::from_json(&mask.to_json().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,426 @@
+use wasm_bindgen::prelude::*;
+
+use crate::hackrf::{SampleRate, SweepPlan, SweepStyle, SAMPLES_PER_BLOCK};
+use crate::sweep::range_bin_count;
+use crate::{Error, SweepAssembler, BYTES_PER_BLOCK};
+
+/// 1回のチューニングでサンプルの受信以外にかかる時間 [s]。
+///
+/// hackrf_sweep の公称値（20 MHz ステップのインターリーブで約 8 GHz/s）から見積もった目安。
+pub const RETUNE_TIME_S: f64 = 0.8e-3;
+pub const MIN_FFT_SIZE: usize = 8;
+/// 1ブロックのサンプル数。この場合 IQ サンプルはヘッダと重なり、ヘッダ部分はサンプル 0 として扱う
+/// （`SweepAssembler::push_transfer`）
+pub const MAX_FFT_SIZE: usize = SAMPLES_PER_BLOCK;
+/// ステップ内で平均するブロック数の上限
+pub const MAX_AVERAGES: u32 = 256;
+
+const _: () = assert!(MAX_FFT_SIZE * 2 <= BYTES_PER_BLOCK);
+
+/// 窓関数の種類
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowKind {
+    Rectangular = 0,
+    Hann = 1,
+    Hamming = 2,
+    /// worker.js と同じ alpha = 0.16 の Blackman 窓
+    Blackman = 3,
+    /// 4項 Blackman-Harris 窓
+    BlackmanHarris = 4,
+    FlatTop = 5,
+}
+
+impl WindowKind {
+    /// 余弦和の係数 a_k（w(x) = Σ (-1)^k a_k cos(2πkx)）
+    fn cosine_terms(self) -> &'static [f64] {
+        match self {
+            WindowKind::Rectangular => &[1.0],
+            WindowKind::Hann => &[0.5, 0.5],
+            WindowKind::Hamming => &[0.54, 0.46],
+            WindowKind::Blackman => &[0.42, 0.5, 0.08],
+            WindowKind::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            WindowKind::FlatTop => &[0.21557895, 0.41663158, 0.277263158, 0.083578947, 0.006947368],
+        }
+    }
+
+    /// `n` 点の窓関数（worker.js と同じく x = i / n の周期的な窓）
+    pub fn coefficients(self, n: usize) -> Vec<f32> {
+        let terms = self.cosine_terms();
+        (0..n)
+            .map(|i| {
+                let x = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+                let w: f64 = terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| if k % 2 == 0 { a * (k as f64 * x).cos() } else { -a * (k as f64 * x).cos() })
+                    .sum();
+                w as f32
+            })
+            .collect()
+    }
+
+    /// `n` 点の窓関数の等価雑音帯域幅 [ビン]
+    pub fn enbw_bins(self, n: usize) -> f64 {
+        let w = self.coefficients(n);
+        let sum: f64 = w.iter().map(|&v| v as f64).sum();
+        let sum_sq: f64 = w.iter().map(|&v| v as f64 * v as f64).sum();
+        n as f64 * sum_sq / (sum * sum)
+    }
+}
+
+/// `n` 点の窓関数を返す（`FFT::new` に渡す）。
+#[wasm_bindgen]
+pub fn window_coefficients(kind: WindowKind, n: usize) -> Vec<f32> {
+    kind.coefficients(n)
+}
+
+/// `n` 点の窓関数の等価雑音帯域幅 [ビン]（FFT サイズから RBW を求めるのに使う）
+#[wasm_bindgen]
+pub fn window_enbw_bins(kind: WindowKind, n: usize) -> f64 {
+    kind.enbw_bins(n)
+}
+
+/// RBW / VBW から決めた掃引の設定。
+///
+/// FFT サイズは、実際の RBW（窓関数の等価雑音帯域幅 × サンプルレート / FFT サイズ）が
+/// 要求以下になる最小の2のべきとする（`MIN_FFT_SIZE..=MAX_FFT_SIZE` に制限するので、
+/// 要求が細かすぎる場合は実際の RBW が要求より大きくなる）。
+/// VBW が RBW より狭い場合は、1ステップで `averages` ブロックを受信して平均する。
+/// 掃引は hackrf_sweep と同じく、ステップ幅 = サンプルレートのインターリーブ掃引で、
+/// 各チューニングの帯域のうち `SweepAssembler` が使う中心から ±1/8..3/8 の範囲で隙間なく覆う
+/// （オフセット 3/8 ステップ）。
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct MeasurementPlan {
+    sample_rate: SampleRate,
+    window: WindowKind,
+    fft_size: usize,
+    rbw_hz: f64,
+    averages: u32,
+    baseband_filter_hz: u32,
+    sweep: SweepPlan,
+}
+
+#[wasm_bindgen]
+impl MeasurementPlan {
+    /// 掃引の設定を決める。
+    ///
+    /// # 引数
+    /// * `start_mhz` - 掃引の下端 [MHz]
+    /// * `stop_mhz` - 掃引の上端 [MHz]。ステップ幅の整数倍になるよう延びる
+    /// * `rbw_hz` - 要求する分解能帯域幅 [Hz]
+    /// * `vbw_hz` - 要求するビデオ帯域幅 [Hz]
+    /// * `window` - 窓関数
+    /// * `sample_rate_hz` - サンプルレート [Hz]（script.js では 20e6）
+    ///
+    /// # エラー
+    /// `with_ranges` と同じ
+    #[wasm_bindgen(constructor)]
+    pub fn new(start_mhz: u16, stop_mhz: u16, rbw_hz: f64, vbw_hz: f64, window: WindowKind, sample_rate_hz: f64) -> Result<MeasurementPlan, Error> {
+        MeasurementPlan::with_ranges(&[start_mhz, stop_mhz], rbw_hz, vbw_hz, window, sample_rate_hz)
+    }
+
+    /// 複数の範囲を1回で掃引する設定を決める。
+    ///
+    /// 範囲は `SweepPlan::new` と同じく周波数順に並べ替え、重なる範囲はまとめる。
+    /// 掃引フレームは範囲ごとのセグメントを周波数順に連結したもの（`assembler`）。
+    ///
+    /// # 引数
+    /// * `ranges_mhz` - 掃引範囲の (下端, 上端) [MHz] を平坦に並べたもの。上端はステップ幅の整数倍になるよう延びる
+    /// * その他 `new` と同じ
+    ///
+    /// # エラー
+    /// * `Error::InvalidParameter` - `ranges_mhz` の長さが奇数の場合
+    /// * `Error::InvalidFilterBandwidth` - `rbw_hz` または `vbw_hz` が正の有限値でない場合
+    /// * `Error::InvalidSampleRate` - `SampleRate::new` が失敗した場合
+    /// * `Error::BandwidthOutOfRange` - サンプルレートに対応するベースバンドフィルタがない場合
+    /// * その他 `SweepPlan::new` と同じ
+    pub fn with_ranges(ranges_mhz: &[u16], rbw_hz: f64, vbw_hz: f64, window: WindowKind, sample_rate_hz: f64) -> Result<MeasurementPlan, Error> {
+        if !ranges_mhz.len().is_multiple_of(2) {
+            return Err(Error::InvalidParameter { name: "Range list length", value: ranges_mhz.len() as f64 });
+        }
+        for bandwidth in [rbw_hz, vbw_hz] {
+            if !bandwidth.is_finite() || bandwidth <= 0.0 {
+                return Err(Error::InvalidFilterBandwidth(bandwidth));
+            }
+        }
+        let sample_rate = SampleRate::new(sample_rate_hz)?;
+        let baseband_filter_hz = sample_rate.baseband_filter_bw()?;
+        let rate = sample_rate.rate_hz();
+
+        let rbw = |n: usize| window.enbw_bins(n) * rate / n as f64;
+        let fft_size = std::iter::successors(Some(MIN_FFT_SIZE), |&n| Some(n * 2))
+            .take_while(|&n| n <= MAX_FFT_SIZE)
+            .find(|&n| rbw(n) <= rbw_hz)
+            .unwrap_or(MAX_FFT_SIZE);
+        let rbw_hz = rbw(fft_size);
+
+        let averages = if vbw_hz >= rbw_hz { 1 } else { ((rbw_hz / vbw_hz).ceil() as u32).min(MAX_AVERAGES) };
+
+        let step_width = rate.round() as u32;
+        let ranges: Vec<(u16, u16)> = ranges_mhz.chunks_exact(2).map(|r| (r[0], r[1])).collect();
+        let sweep = SweepPlan::new(
+            &ranges,
+            averages * BYTES_PER_BLOCK as u32,
+            step_width,
+            step_width / 8 * 3,
+            SweepStyle::Interleaved,
+        )?;
+
+        Ok(MeasurementPlan {
+            sample_rate,
+            window,
+            fft_size,
+            rbw_hz,
+            averages,
+            baseband_filter_hz,
+            sweep,
+        })
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    /// `set_sample_rate_manual` に渡す周波数
+    pub fn sample_rate_freq_hz(&self) -> u32 {
+        self.sample_rate.freq_hz
+    }
+
+    /// `set_sample_rate_manual` に渡す分周比
+    pub fn sample_rate_divider(&self) -> u32 {
+        self.sample_rate.divider
+    }
+
+    /// 実際のサンプルレート [Hz]
+    pub fn sample_rate_hz(&self) -> f64 {
+        self.sample_rate.rate_hz()
+    }
+
+    /// 実際の分解能帯域幅 [Hz]
+    pub fn rbw_hz(&self) -> f64 {
+        self.rbw_hz
+    }
+
+    /// 平均化による実効的なビデオ帯域幅 [Hz]（RBW / 平均回数の目安）
+    pub fn vbw_hz(&self) -> f64 {
+        self.rbw_hz / self.averages as f64
+    }
+
+    /// 1ステップで平均するブロック数
+    pub fn averages(&self) -> u32 {
+        self.averages
+    }
+
+    /// `set_baseband_filter_bandwidth` に渡す帯域幅 [Hz]（`SampleRate::baseband_filter_bw`）
+    pub fn baseband_filter_hz(&self) -> u32 {
+        self.baseband_filter_hz
+    }
+
+    pub fn step_width_hz(&self) -> u32 {
+        self.sweep.step_width()
+    }
+
+    /// `init_sweep` に渡す掃引範囲 [MHz]。各範囲の開始と終了を順に並べたもの（hackrf.js の `initSweep` の形式）
+    pub fn sweep_ranges_mhz(&self) -> Vec<u16> {
+        self.sweep.ranges_mhz().iter().flat_map(|&(start, stop)| [start, stop]).collect()
+    }
+
+    /// `init_sweep` に渡す1ステップのバイト数（`averages` ブロック）
+    pub fn sweep_num_bytes(&self) -> u32 {
+        self.sweep.num_bytes()
+    }
+
+    /// `init_sweep` に渡すオフセット [Hz]
+    pub fn sweep_offset_hz(&self) -> u32 {
+        self.sweep.offset()
+    }
+
+    /// `init_sweep` に渡す掃引スタイル（`SweepStyle` の値）
+    pub fn sweep_style(&self) -> u8 {
+        self.sweep.style() as u8
+    }
+
+    /// ステップ幅の整数倍に延ばした掃引の下端と上端 [MHz]（複数範囲では最初の範囲の下端と最後の範囲の上端）
+    pub fn low_freq_mhz(&self) -> u16 {
+        self.sweep.ranges_mhz()[0].0
+    }
+
+    pub fn high_freq_mhz(&self) -> u16 {
+        self.sweep.ranges_mhz()[self.sweep.ranges_mhz().len() - 1].1
+    }
+
+    /// まとめた後の掃引範囲の数（掃引フレームのセグメント数）
+    pub fn range_count(&self) -> usize {
+        self.sweep.ranges_mhz().len()
+    }
+
+    /// 掃引フレームのビン数（全セグメントの合計）
+    pub fn bin_count(&self) -> usize {
+        let bin_width = self.sample_rate.bin_width_hz(self.fft_size);
+        self.sweep.ranges_mhz().iter().map(|&(start, stop)| range_bin_count(start, stop, bin_width)).sum()
+    }
+
+    /// 1回の掃引にかかる時間の見積もり [s]（チューニング回数 × (受信時間 + 再チューニング時間)）
+    pub fn sweep_time_s(&self) -> f64 {
+        let dwell = self.averages as f64 * SAMPLES_PER_BLOCK as f64 / self.sample_rate.rate_hz() + RETUNE_TIME_S;
+        self.sweep.tunings_per_sweep() as f64 * dwell
+    }
+
+    /// この設定の窓関数
+    pub fn window(&self) -> Vec<f32> {
+        self.window.coefficients(self.fft_size)
+    }
+
+    /// この設定の掃引アセンブラ
+    pub fn assembler(&self) -> Result<SweepAssembler, Error> {
+        SweepAssembler::from_plan(&self.sweep, self.fft_size, &self.sample_rate)
+    }
+}
+
+impl MeasurementPlan {
+    /// `init_sweep` に渡す掃引計画
+    pub fn sweep_plan(&self) -> &SweepPlan {
+        &self.sweep
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows() {
+        // 等価雑音帯域幅の既知の値
+        let enbw = [
+            (WindowKind::Rectangular, 1.0),
+            (WindowKind::Hann, 1.5),
+            (WindowKind::Hamming, 1.363),
+            (WindowKind::Blackman, 1.727),
+            (WindowKind::BlackmanHarris, 2.004),
+            (WindowKind::FlatTop, 3.770),
+        ];
+        for (kind, expected) in enbw {
+            assert!((kind.enbw_bins(4096) - expected).abs() < 2e-3, "{:?}: {}", kind, kind.enbw_bins(4096));
+        }
+
+        // worker.js の Blackman 窓と同じ
+        let n = 16;
+        let alpha = 0.16f64;
+        for (i, &w) in WindowKind::Blackman.coefficients(n).iter().enumerate() {
+            let x = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+            let expected = (1.0 - alpha) / 2.0 - 0.5 * x.cos() + alpha / 2.0 * (2.0 * x).cos();
+            assert!((w as f64 - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = MeasurementPlan::new(2400, 2500, 10e3, 3e3, WindowKind::Blackman, 20e6).unwrap();
+        // 1.727 * 20 MHz / 10 kHz = 3454 → 4096
+        assert_eq!(plan.fft_size(), 4096);
+        assert!((plan.rbw_hz() - 1.727 * 20e6 / 4096.0).abs() < 5.0, "{}", plan.rbw_hz());
+        assert_eq!(plan.averages(), 3);
+        assert!(plan.vbw_hz() <= 3e3);
+        assert_eq!((plan.low_freq_mhz(), plan.high_freq_mhz()), (2400, 2500));
+        assert_eq!(plan.bin_count(), 20480);
+        assert_eq!(plan.sweep_plan().num_bytes(), 3 * BYTES_PER_BLOCK as u32);
+        assert_eq!(plan.sweep_plan().offset(), 7_500_000);
+        assert_eq!(plan.sweep_ranges_mhz(), [2400, 2500]);
+        assert_eq!(plan.sweep_num_bytes(), 3 * BYTES_PER_BLOCK as u32);
+        assert_eq!(plan.sweep_offset_hz(), 7_500_000);
+        assert_eq!(plan.sweep_style(), SweepStyle::Interleaved as u8);
+        // 0.75 * 20 MHz 以上の最初のフィルタ (15 MHz) の1つ下（hackrf.js の setSampleRateManual と同じ）
+        assert_eq!(plan.baseband_filter_hz(), 14_000_000);
+        // 5 ステップ × 2 チューニング × (3 ブロック + 再チューニング)
+        let expected = 10.0 * (3.0 * 8192.0 / 20e6 + RETUNE_TIME_S);
+        assert!((plan.sweep_time_s() - expected).abs() < 1e-9);
+
+        let assembler = plan.assembler().unwrap();
+        assert_eq!(assembler.bin_count(), plan.bin_count());
+        assert_eq!(plan.window().len(), 4096);
+    }
+
+    #[test]
+    fn test_plan_limits() {
+        // 粗い RBW は最小の FFT サイズ、VBW が RBW 以上なら平均しない
+        let plan = MeasurementPlan::new(100, 110, 10e6, 10e6, WindowKind::Hann, 20e6).unwrap();
+        assert_eq!(plan.fft_size(), MIN_FFT_SIZE);
+        assert_eq!(plan.averages(), 1);
+        assert_eq!(plan.high_freq_mhz(), 120);
+
+        // 細かすぎる RBW は最大の FFT サイズに制限する
+        let plan = MeasurementPlan::new(100, 120, 100.0, 0.001, WindowKind::Rectangular, 20e6).unwrap();
+        assert_eq!(plan.fft_size(), MAX_FFT_SIZE);
+        assert!(plan.rbw_hz() > 100.0);
+        assert_eq!(plan.averages(), MAX_AVERAGES);
+
+        // 実際のサンプルレートを使う
+        let plan = MeasurementPlan::new(100, 120, 10e3, 10e3, WindowKind::Hann, 10e6 / 3.0).unwrap();
+        assert_eq!((plan.sample_rate_freq_hz(), plan.sample_rate_divider()), (10_000_000, 3));
+        assert!((plan.rbw_hz() - 1.5 * 10e6 / 3.0 / plan.fft_size() as f64).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_sweep_time_counts_actual_tunings() {
+        // ステップ幅が 4 の倍数でないと、1/4 と 3/4 のステップは切り捨てで進む
+        let dwell = |plan: &MeasurementPlan| SAMPLES_PER_BLOCK as f64 / plan.sample_rate_hz() + RETUNE_TIME_S;
+        let plan = MeasurementPlan::new(1, 22, 1e6, 1e6, WindowKind::Hann, 116_023.0).unwrap();
+        assert_eq!(plan.step_width_hz(), 116_023);
+        assert_eq!(plan.sweep_plan().step_frequencies().len(), 363);
+        assert!((plan.sweep_time_s() - 363.0 * dwell(&plan)).abs() < 1e-9);
+
+        let plan = MeasurementPlan::new(100, 200, 10e3, 10e3, WindowKind::Hann, 10e6 / 3.0).unwrap();
+        assert_eq!(plan.step_width_hz(), 3_333_333);
+        let tunings = plan.sweep_plan().step_frequencies().len() as f64;
+        assert!((plan.sweep_time_s() - tunings * dwell(&plan)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        for (rbw, vbw) in [(0.0, 1.0), (1.0, -1.0), (f64::NAN, 1.0)] {
+            assert!(matches!(
+                MeasurementPlan::new(100, 120, rbw, vbw, WindowKind::Hann, 20e6),
+                Err(Error::InvalidFilterBandwidth(_))
+            ));
+        }
+        assert!(matches!(
+            MeasurementPlan::new(120, 100, 1e3, 1e3, WindowKind::Hann, 20e6),
+            Err(Error::InvalidFrequencyRange { .. })
+        ));
+        assert!(matches!(
+            MeasurementPlan::new(100, 120, 1e3, 1e3, WindowKind::Hann, 0.0),
+            Err(Error::InvalidSampleRate(_))
+        ));
+        assert_eq!(
+            MeasurementPlan::with_ranges(&[100, 120, 400], 1e3, 1e3, WindowKind::Hann, 20e6).err(),
+            Some(Error::InvalidParameter { name: "Range list length", value: 3.0 })
+        );
+        assert_eq!(
+            MeasurementPlan::with_ranges(&[], 1e3, 1e3, WindowKind::Hann, 20e6).err(),
+            Some(Error::InvalidSweepRangeCount(0))
+        );
+    }
+
+    #[test]
+    fn test_multi_range_plan() {
+        // 433, 868, 2400 MHz 付近の3範囲。範囲は周波数順に並び、上端はステップ幅の整数倍に延びる
+        let plan = MeasurementPlan::with_ranges(&[2400, 2480, 433, 435, 863, 870], 20e3, 20e3, WindowKind::Hann, 20e6).unwrap();
+        assert_eq!(plan.range_count(), 3);
+        assert_eq!(plan.sweep_ranges_mhz(), [433, 453, 863, 883, 2400, 2480]);
+        assert_eq!((plan.low_freq_mhz(), plan.high_freq_mhz()), (433, 2480));
+
+        let assembler = plan.assembler().unwrap();
+        assert_eq!(assembler.segment_count(), 3);
+        assert_eq!(assembler.bin_count(), plan.bin_count());
+        let bin_width = plan.sample_rate().bin_width_hz(plan.fft_size());
+        assert_eq!(plan.bin_count(), ((120e6 / bin_width).round()) as usize);
+        // 1 + 1 + 4 ステップ、インターリーブで2倍
+        assert_eq!(plan.sweep_plan().tunings_per_sweep(), 12);
+    }
+}
@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, SweepAssembler};
+
+/// 基準トレースの1つの周波数範囲。`SweepSegment` と同じく、ビン `i` の周波数は
+/// `low_freq_hz + i * (high_freq_hz - low_freq_hz) / levels_db.len()`。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceSegment {
+    pub low_freq_hz: f64,
+    pub high_freq_hz: f64,
+    /// ビンごとの値 [dB]。JSON では NaN（データなし）を null にする
+    #[serde(with = "nan_as_null")]
+    pub levels_db: Vec<f32>,
+}
+
+impl ReferenceSegment {
+    /// `frequency_hz` の値を隣り合うビンの線形補間で求める。範囲外なら `None`。
+    ///
+    /// 最後のビンから上端までは最後のビンの値を使う。
+    fn level_at(&self, frequency_hz: f64) -> Option<f32> {
+        if !(self.low_freq_hz <= frequency_hz && frequency_hz <= self.high_freq_hz) {
+            return None;
+        }
+        let last = self.levels_db.len() - 1;
+        let position = (frequency_hz - self.low_freq_hz) * self.levels_db.len() as f64 / (self.high_freq_hz - self.low_freq_hz);
+        // 同じ周波数軸なら補間せずにビンの値をそのまま使う（隣の NaN を混ぜない）
+        let nearest = position.round();
+        if (position - nearest).abs() < 1e-6 {
+            return Some(self.levels_db[(nearest as usize).min(last)]);
+        }
+        let bin = position.floor() as usize;
+        if bin >= last {
+            return Some(self.levels_db[last]);
+        }
+        let t = (position - bin as f64) as f32;
+        Some(self.levels_db[bin] + (self.levels_db[bin + 1] - self.levels_db[bin]) * t)
+    }
+}
+
+/// 名前付きの基準トレース（「アンテナ接続」と「終端」、対策前と対策後など）
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceTrace {
+    pub name: String,
+    pub segments: Vec<ReferenceSegment>,
+}
+
+impl ReferenceTrace {
+    /// 掃引アセンブラの直前に完成したフレームを基準トレースにする。
+    pub fn from_sweep(name: &str, sweep: &SweepAssembler) -> ReferenceTrace {
+        let segments = sweep
+            .segments()
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ReferenceSegment {
+                low_freq_hz: s.low_freq_hz,
+                high_freq_hz: s.high_freq_hz,
+                levels_db: sweep.completed_segment(i).to_vec(),
+            })
+            .collect();
+        ReferenceTrace { name: name.to_string(), segments }
+    }
+
+    /// `frequency_hz` での値 [dB]。どの範囲にも含まれなければ NaN
+    pub fn level_at(&self, frequency_hz: f64) -> f32 {
+        self.segments.iter().find_map(|s| s.level_at(frequency_hz)).unwrap_or(f32::NAN)
+    }
+
+    /// 読み込んだトレースの周波数軸を確認する（`SweepAssembler::with_segments` と同じ規則）。
+    fn validate(&self) -> Result<(), Error> {
+        if self.segments.is_empty() {
+            return Err(Error::ZeroParameter("Range count"));
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            let (low, high) = (segment.low_freq_hz, segment.high_freq_hz);
+            if !low.is_finite() || !high.is_finite() || low >= high {
+                return Err(Error::InvalidFrequencyRange { low, high });
+            }
+            if segment.levels_db.is_empty() {
+                return Err(Error::ZeroParameter("Bin count"));
+            }
+            if self.segments[..i].iter().any(|s| low <= s.high_freq_hz && s.low_freq_hz <= high) {
+                return Err(Error::InvalidFrequencyRange { low, high });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 名前付きの基準トレースを保存し、ライブのトレースとの差（ライブ − 基準）を求める。
+///
+/// 差は各ライブビンの周波数での基準の値を補間して求めるので、基準を取ったときと
+/// 掃引範囲やビン数が違っていてもよい。基準の範囲外のビンは NaN になる。
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceStore {
+    traces: Vec<ReferenceTrace>,
+}
+
+#[wasm_bindgen]
+impl ReferenceStore {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ReferenceStore {
+        ReferenceStore::default()
+    }
+
+    /// 掃引アセンブラの直前に完成したフレームを `name` で保存する。同じ名前のトレースは置き換える。
+    pub fn capture(&mut self, name: &str, sweep: &SweepAssembler) {
+        self.insert(ReferenceTrace::from_sweep(name, sweep));
+    }
+
+    /// `name` のトレースを削除する。削除した場合は `true` を返す。
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.traces.len();
+        self.traces.retain(|t| t.name != name);
+        self.traces.len() != len
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// 保存されているトレースの名前（保存した順）
+    pub fn names(&self) -> Vec<String> {
+        self.traces.iter().map(|t| t.name.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// `name` のトレースを JSON で書き出す。
+    ///
+    /// # エラー
+    /// * `Error::UnknownReference` - `name` のトレースがない場合
+    pub fn export(&self, name: &str) -> Result<String, Error> {
+        let trace = self.get(name).ok_or_else(|| Error::UnknownReference(name.to_string()))?;
+        Ok(serde_json::to_string(trace).expect("ReferenceTrace is always serializable"))
+    }
+
+    /// `export` で書き出したトレースを読み込む。同じ名前のトレースは置き換える。
+    ///
+    /// # 戻り値
+    /// 読み込んだトレースの名前
+    ///
+    /// # エラー
+    /// * `Error::Json` - JSON として解釈できない場合
+    /// * `Error::ZeroParameter` - 範囲がない、またはビンがない範囲がある場合
+    /// * `Error::InvalidFrequencyRange` - 下端 < 上端 でない範囲、または重なる範囲がある場合
+    pub fn import(&mut self, json: &str) -> Result<String, Error> {
+        let trace: ReferenceTrace = serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))?;
+        trace.validate()?;
+        let name = trace.name.clone();
+        self.insert(trace);
+        Ok(name)
+    }
+
+    /// 全てのトレースを JSON で書き出す。
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("ReferenceStore is always serializable")
+    }
+
+    /// `to_json` で書き出したストアを読み込む。
+    ///
+    /// # エラー
+    /// `import` と同じ
+    pub fn from_json(json: &str) -> Result<ReferenceStore, Error> {
+        let store: ReferenceStore = serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))?;
+        for trace in &store.traces {
+            trace.validate()?;
+        }
+        Ok(store)
+    }
+
+    /// 直前に完成したフレームと `name` の基準との差（ライブ − 基準）を `result` に書き込む。
+    ///
+    /// # エラー
+    /// * `Error::UnknownReference` - `name` のトレースがない場合
+    /// * `Error::OutputLengthMismatch` - `result.len() != sweep.bin_count()` の場合
+    pub fn difference(&self, name: &str, sweep: &SweepAssembler, result: &mut [f32]) -> Result<(), Error> {
+        let mut frequencies = vec![0.0; sweep.bin_count()];
+        if result.len() != frequencies.len() {
+            return Err(Error::OutputLengthMismatch { expected: frequencies.len(), actual: result.len() });
+        }
+        sweep.frequencies(&mut frequencies)?;
+        self.difference_at(name, &frequencies, sweep.completed(), result)
+    }
+
+    /// 周波数 `frequencies_hz` の値 `live` と `name` の基準との差を `result` に書き込む。
+    ///
+    /// # エラー
+    /// * `Error::UnknownReference` - `name` のトレースがない場合
+    /// * `Error::InputLengthMismatch` - `live.len() != frequencies_hz.len()` の場合
+    /// * `Error::OutputLengthMismatch` - `result.len() != frequencies_hz.len()` の場合
+    pub fn difference_at(&self, name: &str, frequencies_hz: &[f64], live: &[f32], result: &mut [f32]) -> Result<(), Error> {
+        let trace = self.get(name).ok_or_else(|| Error::UnknownReference(name.to_string()))?;
+        if live.len() != frequencies_hz.len() {
+            return Err(Error::InputLengthMismatch { expected: frequencies_hz.len(), actual: live.len() });
+        }
+        if result.len() != frequencies_hz.len() {
+            return Err(Error::OutputLengthMismatch { expected: frequencies_hz.len(), actual: result.len() });
+        }
+
+        for ((r, &frequency), &level) in result.iter_mut().zip(frequencies_hz).zip(live) {
+            *r = level - trace.level_at(frequency);
+        }
+        Ok(())
+    }
+}
+
+impl ReferenceStore {
+    pub fn get(&self, name: &str) -> Option<&ReferenceTrace> {
+        self.traces.iter().find(|t| t.name == name)
+    }
+
+    /// トレースを追加する。同じ名前のトレースは置き換える。
+    pub fn insert(&mut self, trace: ReferenceTrace) {
+        match self.traces.iter_mut().find(|t| t.name == trace.name) {
+            Some(existing) => *existing = trace,
+            None => self.traces.push(trace),
+        }
+    }
+}
+
+/// NaN を null として読み書きする（JSON には NaN がないため）
+pub(crate) mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| if v.is_nan() { None } else { Some(*v) }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+        let values = Vec::<Option<f32>>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.unwrap_or(f32::NAN)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100..140 MHz、16 ビンの掃引。120 MHz のブロックの3番目の値だけ `peak`
+    fn sweep(peak: f32) -> SweepAssembler {
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 16).unwrap();
+        let mut spectrum = [-90.0f32; 8];
+        spectrum[2] = peak;
+        sweep.place(100_000_000, &[-90.0; 8]);
+        sweep.place(120_000_000, &spectrum);
+        sweep.place(100_000_000, &[-90.0; 8]);
+        sweep
+    }
+
+    #[test]
+    fn test_difference_same_axis() {
+        let reference = SweepAssembler::new(100e6, 104e6, 4).unwrap();
+        let mut store = ReferenceStore::new();
+        store.capture("terminated", &reference);
+        assert_eq!(store.names(), vec!["terminated".to_string()]);
+
+        // 基準をそのまま置き換える
+        let trace = ReferenceTrace {
+            name: "terminated".to_string(),
+            segments: vec![ReferenceSegment { low_freq_hz: 100e6, high_freq_hz: 104e6, levels_db: vec![-90.0, -80.0, f32::NAN, -60.0] }],
+        };
+        store.insert(trace);
+        assert_eq!(store.len(), 1);
+
+        let frequencies = [100e6, 101e6, 102e6, 103e6];
+        let mut result = [0.0; 4];
+        store.difference_at("terminated", &frequencies, &[-50.0, -50.0, -50.0, -50.0], &mut result).unwrap();
+        assert_eq!(&result[..2], &[40.0, 30.0]);
+        // 基準が NaN のビンは差も NaN（隣のビンと補間しない）
+        assert!(result[2].is_nan());
+        assert_eq!(result[3], 10.0);
+    }
+
+    #[test]
+    fn test_difference_interpolates() {
+        let mut store = ReferenceStore::new();
+        store.insert(ReferenceTrace {
+            name: "before".to_string(),
+            segments: vec![
+                ReferenceSegment { low_freq_hz: 100e6, high_freq_hz: 104e6, levels_db: vec![-80.0, -60.0, -40.0, -20.0] },
+                ReferenceSegment { low_freq_hz: 200e6, high_freq_hz: 201e6, levels_db: vec![-10.0] },
+            ],
+        });
+
+        // ビン幅が半分のライブ掃引、基準の範囲外と範囲の間を含む
+        let frequencies = [99e6, 100e6, 100.5e6, 101.25e6, 103.5e6, 104e6, 150e6, 200.5e6];
+        let live = [0.0; 8];
+        let mut result = [0.0; 8];
+        store.difference_at("before", &frequencies, &live, &mut result).unwrap();
+        assert!(result[0].is_nan());
+        assert_eq!(result[1], 80.0);
+        assert_eq!(result[2], 70.0);
+        assert_eq!(result[3], 55.0);
+        // 最後のビンから上端までは最後のビンの値
+        assert_eq!(result[4], 20.0);
+        assert_eq!(result[5], 20.0);
+        assert!(result[6].is_nan());
+        assert_eq!(result[7], 10.0);
+    }
+
+    #[test]
+    fn test_difference_from_sweep() {
+        let reference = sweep(-90.0);
+        let mut store = ReferenceStore::new();
+        store.capture("terminated", &reference);
+
+        let live = sweep(-30.0);
+        let mut result = vec![0.0; live.bin_count()];
+        store.difference("terminated", &live, &mut result).unwrap();
+        // ビン 9（122.5 MHz）だけが 60 dB 高く、データのないビンは NaN のまま
+        assert_eq!(result[9], 60.0);
+        assert_eq!(result.iter().filter(|&&v| v == 0.0).count(), 11);
+        assert_eq!(result.iter().filter(|v| v.is_nan()).count(), 4);
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut store = ReferenceStore::new();
+        store.insert(ReferenceTrace {
+            name: "antenna".to_string(),
+            segments: vec![ReferenceSegment { low_freq_hz: 1e6, high_freq_hz: 2e6, levels_db: vec![-50.0, f32::NAN] }],
+        });
+
+        let json = store.export("antenna").unwrap();
+        assert_eq!(json, r#"{"name":"antenna","segments":[{"low_freq_hz":1000000.0,"high_freq_hz":2000000.0,"levels_db":[-50.0,null]}]}"#);
+
+        let mut imported = ReferenceStore::new();
+        assert_eq!(imported.import(&json).unwrap(), "antenna");
+        assert_eq!(imported.export("antenna").unwrap(), json);
+        assert!(imported.get("antenna").unwrap().segments[0].levels_db[1].is_nan());
+
+        let restored = ReferenceStore::from_json(&store.to_json()).unwrap();
+        assert_eq!(restored.to_json(), store.to_json());
+
+        assert!(store.remove("antenna"));
+        assert!(!store.remove("antenna"));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut store = ReferenceStore::new();
+        assert_eq!(store.export("missing"), Err(Error::UnknownReference("missing".to_string())));
+        assert_eq!(
+            store.difference_at("missing", &[], &[], &mut []),
+            Err(Error::UnknownReference("missing".to_string()))
+        );
+        assert!(matches!(store.import("[]"), Err(Error::Json(_))));
+        assert_eq!(store.import(r#"{"name":"a","segments":[]}"#), Err(Error::ZeroParameter("Range count")));
+        assert_eq!(
+            store.import(r#"{"name":"a","segments":[{"low_freq_hz":2,"high_freq_hz":1,"levels_db":[0]}]}"#),
+            Err(Error::InvalidFrequencyRange { low: 2.0, high: 1.0 })
+        );
+        assert_eq!(
+            store.import(r#"{"name":"a","segments":[{"low_freq_hz":1,"high_freq_hz":2,"levels_db":[]}]}"#),
+            Err(Error::ZeroParameter("Bin count"))
+        );
+        assert!(store.is_empty());
+
+        store.capture("a", &SweepAssembler::new(1e6, 2e6, 4).unwrap());
+        assert_eq!(store.difference_at("a", &[1e6], &[], &mut [0.0]), Err(Error::InputLengthMismatch { expected: 1, actual: 0 }));
+        let sweep = SweepAssembler::new(1e6, 2e6, 4).unwrap();
+        assert_eq!(store.difference("a", &sweep, &mut [0.0; 3]), Err(Error::OutputLengthMismatch { expected: 4, actual: 3 }));
+    }
+}
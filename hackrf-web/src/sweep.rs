@@ -0,0 +1,1056 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::hackrf::{step_frequencies, SampleRate, SweepPlan, SweepStyle};
+use crate::{CalibrationSet, CalibrationTable, Error, FFT};
+
+/// HackRF の掃引モードでの1ブロックのバイト数
+pub const BYTES_PER_BLOCK: usize = 16384;
+/// ブロック先頭のヘッダ長（マジック 0x7F 0x7F と 64bit 周波数）
+pub const BLOCK_HEADER_LEN: usize = 10;
+
+/// 掃引モードのブロックヘッダから中心周波数 [Hz] を読み出す。
+///
+/// ヘッダは `0x7F 0x7F` に続くリトルエンディアンの u64。
+/// マジックが一致しない、またはブロックがヘッダより短い場合は `None` を返す。
+pub fn block_frequency(block: &[u8]) -> Option<u64> {
+    if block.len() < BLOCK_HEADER_LEN || block[0] != 0x7F || block[1] != 0x7F {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&block[2..BLOCK_HEADER_LEN]);
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// 掃引フレーム内の1つの周波数範囲。
+///
+/// フレームのビン `offset..offset + bin_count` がこの範囲に対応し、
+/// `low_freq_hz` から `bin_width_hz` 間隔の周波数軸を持つ（`high_freq_hz` は最後のビンの上端）。
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SweepSegment {
+    pub low_freq_hz: f64,
+    pub high_freq_hz: f64,
+    /// ビン幅 [Hz]。`from_plan` では実際のサンプルレートから求めた FFT のビン幅そのもの
+    pub bin_width_hz: f64,
+    /// フレーム内の先頭ビン
+    pub offset: usize,
+    pub bin_count: usize,
+}
+
+impl SweepSegment {
+    /// セグメント内のビン `bin` の周波数 [Hz]
+    pub fn bin_frequency(&self, bin: usize) -> f64 {
+        self.low_freq_hz + bin as f64 * self.bin_width_hz
+    }
+
+    fn contains(&self, frequency: f64) -> bool {
+        self.low_freq_hz <= frequency && frequency <= self.high_freq_hz
+    }
+}
+
+/// `segments` が長さ `len` のフレームを先頭から隙間なく覆っているか確かめる。
+///
+/// # エラー
+/// * `Error::InvalidParameter` - セグメントの `offset` が直前のセグメントの末尾と一致しない場合
+/// * `Error::InputLengthMismatch` - `len` がセグメントのビン数の合計と一致しない場合
+pub(crate) fn check_segments(segments: &[SweepSegment], len: usize) -> Result<(), Error> {
+    let mut end = 0usize;
+    for segment in segments {
+        if segment.offset != end {
+            return Err(Error::InvalidParameter { name: "Segment offset", value: segment.offset as f64 });
+        }
+        end = end.saturating_add(segment.bin_count);
+    }
+    if len != end {
+        return Err(Error::InputLengthMismatch { expected: end, actual: len });
+    }
+    Ok(())
+}
+
+/// 範囲 `start_mhz..stop_mhz` をビン幅 `bin_width_hz` で覆うビン数（スパン / ビン幅を丸めたもの。最低 1）
+pub(crate) fn range_bin_count(start_mhz: u16, stop_mhz: u16, bin_width_hz: f64) -> usize {
+    (((stop_mhz - start_mhz) as f64 * 1e6 / bin_width_hz).round() as usize).max(1)
+}
+
+/// 掃引ストリームの異常の累計
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SweepMetrics {
+    /// ヘッダが無効で読み捨てたブロック
+    pub invalid_blocks: u32,
+    /// どの範囲にも含まれない周波数のブロック
+    pub out_of_range_blocks: u32,
+    /// 期待するステップの順序にない周波数のブロック
+    pub unexpected_blocks: u32,
+    /// 受信されなかったステップ
+    pub dropped_steps: u32,
+    /// 同じ掃引の中で再び受信したステップ
+    pub repeated_steps: u32,
+    /// ステップが飛んだ回数（USB 転送の取りこぼし）
+    pub overruns: u32,
+    /// 欠けたステップがあるまま完了した掃引
+    pub incomplete_sweeps: u32,
+}
+
+/// 1回の掃引の欠落と重複
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SweepStatus {
+    pub missing_steps: u32,
+    pub repeated_steps: u32,
+}
+
+/// 表示フレームにまとめる掃引の検波方式
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameDetector {
+    /// ビンごとの最大値（短いバーストも残る）
+    #[default]
+    Max = 0,
+    /// ビンごとの dB 値の平均
+    Mean = 1,
+}
+
+/// 掃引モードの各ブロックの FFT 結果を、1つの掃引フレームに並べる。
+///
+/// フレームは掃引範囲ごとのセグメントを範囲の順に連結したもので、
+/// 各ブロックの FFT 結果のうち、中心から ±(1/8..3/8) の範囲（DC スパイクと帯域端を除く）を
+/// ヘッダの周波数を含むセグメントの対応する位置に書き込む。
+/// 最初の範囲の下端のブロックが来たら掃引1回分の完了とし（ファームウェアは範囲を順に掃引する）、
+/// それまでのフレームを完成したフレームとして保持する。
+///
+/// どのブロックからも書き込まれなかったビンは NaN（データなし）になる。
+/// ステップの順序（`from_plan` または `track_steps`）を与えると、ブロックのヘッダ周波数を
+/// 期待する順序と照合し、欠けたステップや重複したステップを掃引ごとに記録する。
+///
+/// 表示のフレームレートより掃引が速い場合のため、完成した掃引は `take_frame` で取り出すまで
+/// `FrameDetector` で1つの表示フレームにまとめられる（間引いた掃引の過渡信号も失われない）。
+///
+/// 校正テーブル（`set_calibration` / `select_calibration`）を設定すると、完成したフレームの
+/// 各セグメントにそのセグメントの周波数軸で補正量を加えてから、表示フレームにまとめる。
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SweepAssembler {
+    segments: Vec<SweepSegment>,
+    /// 組み立て中のフレーム
+    line: Vec<f32>,
+    /// 直前に完成したフレーム
+    completed: Vec<f32>,
+    sweep_count: u32,
+    /// `push_transfer` 用の FFT 出力バッファ
+    spectra: Vec<f32>,
+    /// IQ サンプルがヘッダと重なる場合に、ヘッダを 0 にした転送バッファの写し
+    masked: Vec<u8>,
+    /// 1回の掃引で期待するヘッダ周波数の順序（空なら照合しない）
+    steps: Vec<u64>,
+    /// 1ステップのブロック数
+    blocks_per_step: usize,
+    /// 次に期待するステップ。最初の掃引の開始までは `None`
+    next_step: Option<usize>,
+    /// 直前のブロックの周波数と、その周波数が連続したブロック数
+    last_frequency: Option<u64>,
+    run: usize,
+    status: SweepStatus,
+    completed_status: SweepStatus,
+    metrics: SweepMetrics,
+    detector: FrameDetector,
+    /// `take_frame` 以降に完成した掃引をまとめたもの（Max は最大値、Mean は合計）
+    frame: Vec<f32>,
+    /// `frame` の各ビンにまとめたデータのある掃引の数
+    frame_counts: Vec<u32>,
+    frame_sweeps: u32,
+    /// 完成したフレームに適用する校正テーブル
+    calibration: Option<CalibrationTable>,
+}
+
+#[wasm_bindgen]
+impl SweepAssembler {
+    /// 単一範囲の掃引アセンブラを作成する。
+    ///
+    /// # 引数
+    /// * `low_freq_hz` - 掃引の下端 [Hz]。このヘッダ周波数のブロックで掃引が完了する
+    /// * `high_freq_hz` - 掃引の上端 [Hz]
+    /// * `bin_count` - ラインのビン数
+    ///
+    /// # エラー
+    /// * `Error::InvalidFrequencyRange` - `low_freq_hz < high_freq_hz` でない場合
+    /// * `Error::ZeroParameter` - `bin_count` が 0 の場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(low_freq_hz: f64, high_freq_hz: f64, bin_count: usize) -> Result<SweepAssembler, Error> {
+        SweepAssembler::with_segments(&[(low_freq_hz, high_freq_hz, bin_count)])
+    }
+
+    /// 複数範囲の掃引アセンブラを作成する。
+    ///
+    /// # 引数
+    /// * `ranges_hz` - 掃引範囲の (下端, 上端) [Hz] を平坦に並べたもの。掃引の順に並べる
+    /// * `bin_counts` - 範囲ごとのビン数
+    ///
+    /// # エラー
+    /// * `Error::InputLengthMismatch` - `ranges_hz.len() != bin_counts.len() * 2` の場合
+    /// * その他 `with_segments` と同じ
+    pub fn with_ranges(ranges_hz: &[f64], bin_counts: &[u32]) -> Result<SweepAssembler, Error> {
+        if ranges_hz.len() != bin_counts.len() * 2 {
+            return Err(Error::InputLengthMismatch { expected: bin_counts.len() * 2, actual: ranges_hz.len() });
+        }
+        let ranges: Vec<(f64, f64, usize)> =
+            ranges_hz.chunks_exact(2).zip(bin_counts).map(|(r, &bins)| (r[0], r[1], bins as usize)).collect();
+        SweepAssembler::with_segments(&ranges)
+    }
+
+    /// ステップの順序を与え、欠落と重複の検出を有効にする。
+    ///
+    /// 順序は各範囲をファームウェアと同じ規則で掃引したものとする（`SweepPlan::step_frequencies`）。
+    ///
+    /// # 引数
+    /// * `step_width_hz` - ステップ幅 [Hz]
+    /// * `interleaved` - インターリーブ掃引か
+    /// * `blocks_per_step` - 1ステップのブロック数（`num_bytes / BYTES_PER_BLOCK`）
+    ///
+    /// # エラー
+    /// * `Error::ZeroParameter` - `step_width_hz` または `blocks_per_step` が 0 の場合
+    pub fn track_steps(&mut self, step_width_hz: u32, interleaved: bool, blocks_per_step: usize) -> Result<(), Error> {
+        if step_width_hz == 0 {
+            return Err(Error::ZeroParameter("Step width"));
+        }
+        if blocks_per_step == 0 {
+            return Err(Error::ZeroParameter("Blocks per step"));
+        }
+        let style = if interleaved { SweepStyle::Interleaved } else { SweepStyle::Linear };
+        let ranges = self.segments.iter().map(|s| (s.low_freq_hz as u64, s.high_freq_hz as u64));
+        self.steps = step_frequencies(ranges, step_width_hz as u64, style);
+        self.blocks_per_step = blocks_per_step;
+        self.next_step = None;
+        Ok(())
+    }
+
+    /// フレーム全体のビン数
+    pub fn bin_count(&self) -> usize {
+        self.line.len()
+    }
+
+    /// 完了した掃引の数
+    pub fn sweep_count(&self) -> u32 {
+        self.sweep_count
+    }
+
+    /// 異常の累計
+    pub fn metrics(&self) -> SweepMetrics {
+        self.metrics
+    }
+
+    /// 直前に完成したフレームの欠落と重複
+    pub fn completed_status(&self) -> SweepStatus {
+        self.completed_status
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// セグメント `segment` のフレーム内の先頭ビン
+    pub fn segment_offset(&self, segment: usize) -> Option<usize> {
+        self.segments.get(segment).map(|s| s.offset)
+    }
+
+    pub fn segment_bin_count(&self, segment: usize) -> Option<usize> {
+        self.segments.get(segment).map(|s| s.bin_count)
+    }
+
+    pub fn segment_low_freq(&self, segment: usize) -> Option<f64> {
+        self.segments.get(segment).map(|s| s.low_freq_hz)
+    }
+
+    pub fn segment_high_freq(&self, segment: usize) -> Option<f64> {
+        self.segments.get(segment).map(|s| s.high_freq_hz)
+    }
+
+    pub fn segment_bin_width(&self, segment: usize) -> Option<f64> {
+        self.segments.get(segment).map(|s| s.bin_width_hz)
+    }
+
+    /// フレームの各ビンの周波数 [Hz] を `result` に書き込む。
+    ///
+    /// # エラー
+    /// * `Error::OutputLengthMismatch` - `result.len() != bin_count` の場合
+    pub fn frequencies(&self, result: &mut [f64]) -> Result<(), Error> {
+        if result.len() != self.line.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.line.len(), actual: result.len() });
+        }
+        for segment in &self.segments {
+            for (bin, r) in result[segment.offset..segment.offset + segment.bin_count].iter_mut().enumerate() {
+                *r = segment.bin_frequency(bin);
+            }
+        }
+        Ok(())
+    }
+
+    /// 表示フレームの検波方式を設定する。まとめかけの掃引は破棄する。
+    pub fn set_frame_detector(&mut self, detector: FrameDetector) {
+        self.detector = detector;
+        self.clear_frame();
+    }
+
+    pub fn frame_detector(&self) -> FrameDetector {
+        self.detector
+    }
+
+    /// 完成した掃引のフレームに、各セグメントの周波数軸で校正テーブルを適用する（次の掃引から）。
+    pub fn set_calibration(&mut self, table: &CalibrationTable) {
+        self.calibration = Some(table.clone());
+    }
+
+    /// 校正テーブルの適用をやめる。
+    pub fn clear_calibration(&mut self) {
+        self.calibration = None;
+    }
+
+    /// 現在のゲイン設定に対応するテーブルを `set` から選び、完成した掃引に適用する。
+    /// 使えるテーブルがない場合は校正をやめ、`false` を返す。ゲインを変えるたびに呼ぶ。
+    pub fn select_calibration(&mut self, set: &CalibrationSet, lna_gain: u32, vga_gain: u32, amp_enable: bool) -> bool {
+        self.calibration = set.lookup(lna_gain, vga_gain, amp_enable).cloned();
+        self.calibration.is_some()
+    }
+
+    pub fn has_calibration(&self) -> bool {
+        self.calibration.is_some()
+    }
+
+    /// 前回の `take_frame` 以降に完成し、表示フレームにまとめた掃引の数
+    pub fn pending_sweeps(&self) -> u32 {
+        self.frame_sweeps
+    }
+
+    /// 前回の `take_frame` 以降に完成した全ての掃引を検波方式でまとめて `result` に書き込む。
+    ///
+    /// まとめた掃引はクリアする。どの掃引にもデータのないビンは NaN。
+    /// 完成した掃引がなければ `result` は変更しない。
+    ///
+    /// # 戻り値
+    /// まとめた掃引の数
+    ///
+    /// # エラー
+    /// * `Error::OutputLengthMismatch` - `result.len() != bin_count` の場合
+    pub fn take_frame(&mut self, result: &mut [f32]) -> Result<u32, Error> {
+        if result.len() != self.frame.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.frame.len(), actual: result.len() });
+        }
+        let sweeps = self.frame_sweeps;
+        if sweeps == 0 {
+            return Ok(0);
+        }
+        for ((r, &v), &count) in result.iter_mut().zip(&self.frame).zip(&self.frame_counts) {
+            *r = match (count, self.detector) {
+                (0, _) => f32::NAN,
+                (_, FrameDetector::Max) => v,
+                (_, FrameDetector::Mean) => v / count as f32,
+            };
+        }
+        self.clear_frame();
+        Ok(sweeps)
+    }
+
+    /// 直前に完成したフレームを `result` に書き込む。データのないビンは NaN。
+    ///
+    /// # エラー
+    /// * `Error::OutputLengthMismatch` - `result.len() != bin_count` の場合
+    pub fn completed_line(&self, result: &mut [f32]) -> Result<(), Error> {
+        if result.len() != self.completed.len() {
+            return Err(Error::OutputLengthMismatch { expected: self.completed.len(), actual: result.len() });
+        }
+        result.copy_from_slice(&self.completed);
+        Ok(())
+    }
+
+    /// USB 転送バッファ1つ分のブロックを FFT し、掃引フレームに並べる。
+    ///
+    /// FFT は `fft_batch` でまとめて行う（`parallel` フィーチャ有効時は並列）。
+    /// フレームへの配置はブロックの順に行うので、結果は逐次処理と同じになる。
+    /// ヘッダが無効なブロックと掃引範囲外のブロックは読み捨てる。
+    ///
+    /// # 引数
+    /// * `fft` - 使用する FFT。IQ サンプルは各ブロックの末尾 `fft.n * 2` バイト。
+    ///   FFT サイズが `SAMPLES_PER_BLOCK` (8192) の場合はブロック全体で、先頭のヘッダ部分は
+    ///   サンプル 0 として扱う
+    /// * `transfer` - 転送バッファ。`BYTES_PER_BLOCK` ごとのブロックからなる
+    ///
+    /// # 戻り値
+    /// この転送で完了した掃引の数
+    ///
+    /// # エラー
+    /// * `Error::PayloadOutOfBlock` - FFT サイズが1ブロックのサンプル数より大きい場合
+    pub fn push_transfer(&mut self, fft: &mut FFT, transfer: &[u8]) -> Result<u32, Error> {
+        let n = fft.size();
+        let payload_len = n * 2;
+        if payload_len > BYTES_PER_BLOCK {
+            return Err(Error::PayloadOutOfBlock { offset: 0, length: payload_len, stride: BYTES_PER_BLOCK });
+        }
+
+        let blocks = transfer.len() / BYTES_PER_BLOCK;
+        let mut spectra = std::mem::take(&mut self.spectra);
+        spectra.resize(blocks * n, 0.0);
+        let r = if payload_len > BYTES_PER_BLOCK - BLOCK_HEADER_LEN {
+            // ヘッダの値をサンプルとして FFT に入れないよう、写しのヘッダを 0 にする
+            let mut masked = std::mem::take(&mut self.masked);
+            masked.clear();
+            masked.extend_from_slice(&transfer[..blocks * BYTES_PER_BLOCK]);
+            for block in masked.chunks_exact_mut(BYTES_PER_BLOCK) {
+                block[..BLOCK_HEADER_LEN].fill(0);
+            }
+            let r = fft.fft_batch(as_i8(&masked), BYTES_PER_BLOCK, BYTES_PER_BLOCK - payload_len, &mut spectra);
+            self.masked = masked;
+            r
+        } else {
+            fft.fft_batch(as_i8(transfer), BYTES_PER_BLOCK, BYTES_PER_BLOCK - payload_len, &mut spectra)
+        };
+
+        let mut completed = 0;
+        if r.is_ok() {
+            for (block, spectrum) in transfer.chunks_exact(BYTES_PER_BLOCK).zip(spectra.chunks_exact(n)) {
+                match block_frequency(block) {
+                    Some(frequency) => {
+                        if self.place(frequency, spectrum) {
+                            completed += 1;
+                        }
+                    }
+                    None => self.metrics.invalid_blocks += 1,
+                }
+            }
+        }
+        self.spectra = spectra;
+        r.map(|_| completed)
+    }
+
+    /// 組み立て中のフレームと完成したフレームをデータなし（NaN）に戻し、カウンタを 0 にする。
+    pub fn reset(&mut self) {
+        self.line.fill(f32::NAN);
+        self.completed.fill(f32::NAN);
+        self.sweep_count = 0;
+        self.next_step = None;
+        self.last_frequency = None;
+        self.run = 0;
+        self.status = SweepStatus::default();
+        self.completed_status = SweepStatus::default();
+        self.metrics = SweepMetrics::default();
+        self.clear_frame();
+    }
+}
+
+impl SweepAssembler {
+    /// 範囲ごとにビン数を指定して掃引アセンブラを作成する。
+    ///
+    /// # 引数
+    /// * `ranges` - (下端 [Hz], 上端 [Hz], ビン数) のリスト。掃引の順に並べる
+    ///
+    /// # エラー
+    /// * `Error::InvalidFrequencyRange` - 下端 < 上端 でない範囲、または他の範囲と重なる範囲がある場合
+    /// * `Error::ZeroParameter` - 範囲がない、またはビン数が 0 の範囲がある場合
+    pub fn with_segments(ranges: &[(f64, f64, usize)]) -> Result<SweepAssembler, Error> {
+        if ranges.is_empty() {
+            return Err(Error::ZeroParameter("Range count"));
+        }
+        let mut segments = Vec::with_capacity(ranges.len());
+        let mut offset = 0;
+        for &(low, high, bin_count) in ranges {
+            if low.is_nan() || high.is_nan() || low >= high {
+                return Err(Error::InvalidFrequencyRange { low, high });
+            }
+            if bin_count == 0 {
+                return Err(Error::ZeroParameter("Bin count"));
+            }
+            // 端点を共有する範囲はどちらに置くか決まらないので、重なりとして扱う
+            if segments.iter().any(|s: &SweepSegment| low <= s.high_freq_hz && s.low_freq_hz <= high) {
+                return Err(Error::InvalidFrequencyRange { low, high });
+            }
+            segments.push(SweepSegment { low_freq_hz: low, high_freq_hz: high, bin_width_hz: (high - low) / bin_count as f64, offset, bin_count });
+            offset += bin_count;
+        }
+
+        Ok(SweepAssembler {
+            segments,
+            line: vec![f32::NAN; offset],
+            completed: vec![f32::NAN; offset],
+            sweep_count: 0,
+            spectra: Vec::new(),
+            masked: Vec::new(),
+            steps: Vec::new(),
+            blocks_per_step: 1,
+            next_step: None,
+            last_frequency: None,
+            run: 0,
+            status: SweepStatus::default(),
+            completed_status: SweepStatus::default(),
+            metrics: SweepMetrics::default(),
+            detector: FrameDetector::default(),
+            frame: vec![0.0; offset],
+            frame_counts: vec![0; offset],
+            frame_sweeps: 0,
+            calibration: None,
+        })
+    }
+
+    /// 掃引計画の範囲ごとに、FFT の分解能（実際のサンプルレート / `fft_size`）でセグメントを作る。
+    /// ステップの順序も掃引計画から設定する。
+    ///
+    /// ビン幅は要求したレートやステップ幅ではなく、`HackRF::set_sample_rate` が返した
+    /// 実際のレートから求める。各範囲のビン数はスパンをビン幅で割って丸め、上端は
+    /// 下端からビン数分のビン幅の位置にする（範囲の終端とは最大で半ビンずれる）。
+    ///
+    /// # エラー
+    /// * `Error::ZeroParameter` - `fft_size` が 0 の場合
+    pub fn from_plan(plan: &SweepPlan, fft_size: usize, sample_rate: &SampleRate) -> Result<SweepAssembler, Error> {
+        if fft_size == 0 {
+            return Err(Error::ZeroParameter("FFT size"));
+        }
+        let bin_width = sample_rate.bin_width_hz(fft_size);
+        let ranges: Vec<(f64, f64, usize)> = plan
+            .ranges_mhz()
+            .iter()
+            .map(|&(start, stop)| {
+                let bins = range_bin_count(start, stop, bin_width);
+                let low = start as f64 * 1e6;
+                (low, low + bins as f64 * bin_width, bins)
+            })
+            .collect();
+        let mut assembler = SweepAssembler::with_segments(&ranges)?;
+        // (high - low) / bins は丸め誤差で FFT のビン幅とずれるので、ビン幅そのものを使う
+        for segment in &mut assembler.segments {
+            segment.bin_width_hz = bin_width;
+        }
+        assembler.steps = plan.step_frequencies();
+        assembler.blocks_per_step = plan.num_bytes() as usize / BYTES_PER_BLOCK;
+        Ok(assembler)
+    }
+
+    pub fn segments(&self) -> &[SweepSegment] {
+        &self.segments
+    }
+
+    /// ヘッダ周波数 `frequency_hz` のブロックの FFT 結果（DC 中心配置）をフレームに書き込む。
+    ///
+    /// どの範囲にも含まれない周波数は無視する。このブロックで掃引が完了した場合は `true` を返す。
+    /// 1ステップが複数ブロックの場合、同じ周波数の続きのブロックは同じステップとして扱い、
+    /// ステップ内のブロックの dB 値を平均する（VBW に相当する平均化）。
+    pub fn place(&mut self, frequency_hz: u64, spectrum: &[f32]) -> bool {
+        let frequency = frequency_hz as f64;
+        let Some(segment) = self.segments.iter().copied().find(|s| s.contains(frequency)) else {
+            self.metrics.out_of_range_blocks += 1;
+            return false;
+        };
+
+        let continuation = self.last_frequency == Some(frequency_hz) && self.run < self.blocks_per_step;
+        self.run = if continuation { self.run + 1 } else { 1 };
+        self.last_frequency = Some(frequency_hz);
+
+        let completed = !continuation && frequency == self.segments[0].low_freq_hz;
+        if completed {
+            self.finish_sweep();
+        }
+        if !continuation {
+            self.track(frequency_hz);
+        }
+
+        let n = spectrum.len();
+        let pos = ((frequency - segment.low_freq_hz) / segment.bin_width_hz) as usize;
+
+        // 中心から見て負側 1/8..3/8 と正側 5/8..7/8 の範囲（両端を含む）
+        let low = &spectrum[n / 8..((3 * n).div_ceil(8) + 1).min(n)];
+        let high = &spectrum[5 * n / 8..((7 * n).div_ceil(8) + 1).min(n)];
+        // n が 8 の倍数でない場合も、2つの範囲の距離はスライスの開始位置の差に合わせる
+        let pos2 = pos + 5 * n / 8 - n / 8;
+        self.write(&segment, pos, low);
+        self.write(&segment, pos2, high);
+
+        completed
+    }
+
+    /// 直前に完成したフレーム
+    pub fn completed(&self) -> &[f32] {
+        &self.completed
+    }
+
+    /// 直前に完成したフレームのうち、セグメント `segment` の部分
+    ///
+    /// # パニック
+    /// `segment >= segment_count()` の場合
+    pub fn completed_segment(&self, segment: usize) -> &[f32] {
+        let s = &self.segments[segment];
+        &self.completed[s.offset..s.offset + s.bin_count]
+    }
+
+    /// 組み立て中のフレームを完成したフレームにし、次の掃引を始める。
+    fn finish_sweep(&mut self) {
+        if let Some(next) = self.next_step {
+            // 掃引の末尾で欠けたステップ
+            if next < self.steps.len() {
+                self.drop_steps((self.steps.len() - next) as u32);
+            }
+            if self.status.missing_steps > 0 {
+                self.metrics.incomplete_sweeps += 1;
+            }
+        }
+        if !self.steps.is_empty() {
+            self.next_step = Some(0);
+        }
+
+        self.sweep_count += 1;
+        self.completed.copy_from_slice(&self.line);
+        if let Some(table) = &self.calibration {
+            // セグメントはフレームを隙間なく覆っているので失敗しない
+            let applied = table.apply_segments(&mut self.completed, &self.segments);
+            debug_assert!(applied.is_ok());
+        }
+        self.line.fill(f32::NAN);
+        self.completed_status = std::mem::take(&mut self.status);
+        self.fold_completed();
+    }
+
+    /// 完成したフレームを表示フレームにまとめる。データのないビンは数えない。
+    fn fold_completed(&mut self) {
+        for ((acc, count), &v) in self.frame.iter_mut().zip(&mut self.frame_counts).zip(&self.completed) {
+            if v.is_nan() {
+                continue;
+            }
+            *acc = match (*count, self.detector) {
+                (0, _) => v,
+                (_, FrameDetector::Max) => acc.max(v),
+                (_, FrameDetector::Mean) => *acc + v,
+            };
+            *count += 1;
+        }
+        self.frame_sweeps += 1;
+    }
+
+    fn clear_frame(&mut self) {
+        self.frame.fill(0.0);
+        self.frame_counts.fill(0);
+        self.frame_sweeps = 0;
+    }
+
+    /// ステップの周波数を期待する順序と照合する。
+    fn track(&mut self, frequency_hz: u64) {
+        let Some(next) = self.next_step else {
+            return;
+        };
+        match self.steps.iter().position(|&f| f == frequency_hz) {
+            Some(step) if step == next => self.next_step = Some(next + 1),
+            Some(step) if step > next => {
+                self.drop_steps((step - next) as u32);
+                self.next_step = Some(step + 1);
+            }
+            Some(_) => {
+                self.status.repeated_steps += 1;
+                self.metrics.repeated_steps += 1;
+            }
+            None => self.metrics.unexpected_blocks += 1,
+        }
+    }
+
+    fn drop_steps(&mut self, count: u32) {
+        self.status.missing_steps += count;
+        self.metrics.dropped_steps += count;
+        self.metrics.overruns += 1;
+    }
+
+    /// `values` をセグメント内の `pos` から書き込む。セグメントをはみ出す部分は捨てる。
+    ///
+    /// ステップの2ブロック目以降では、それまでのブロックとの平均を書き込む。
+    fn write(&mut self, segment: &SweepSegment, pos: usize, values: &[f32]) {
+        if pos < segment.bin_count {
+            let len = values.len().min(segment.bin_count - pos);
+            let start = segment.offset + pos;
+            let line = &mut self.line[start..start + len];
+            if self.run > 1 {
+                let weight = 1.0 / self.run as f32;
+                for (l, &v) in line.iter_mut().zip(values) {
+                    *l = if l.is_nan() { v } else { *l + (v - *l) * weight };
+                }
+            } else {
+                line.copy_from_slice(&values[..len]);
+            }
+        }
+    }
+}
+
+/// USB 転送バッファ（u8）を IQ サンプル（i8）として再解釈する（ゼロコピー）。
+fn as_i8(transfer: &[u8]) -> &[i8] {
+    // u8 と i8 はサイズ・アラインメントが同じ
+    unsafe { std::slice::from_raw_parts(transfer.as_ptr() as *const i8, transfer.len()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hackrf::SweepStyle;
+
+    /// ヘッダ周波数 `frequency` と、末尾に `n` サンプルの複素正弦波を持つブロックを追加する。
+    fn push_block(transfer: &mut Vec<u8>, frequency: u64, n: usize, tone_bin: usize) {
+        let start = transfer.len();
+        transfer.resize(start + BYTES_PER_BLOCK, 0);
+        let block = &mut transfer[start..];
+        block[0] = 0x7F;
+        block[1] = 0x7F;
+        block[2..BLOCK_HEADER_LEN].copy_from_slice(&frequency.to_le_bytes());
+        let payload = &mut block[BYTES_PER_BLOCK - n * 2..];
+        for i in 0..n {
+            let phase = 2.0 * std::f32::consts::PI * (tone_bin * i) as f32 / n as f32;
+            payload[i * 2] = (phase.cos() * 100.0) as i8 as u8;
+            payload[i * 2 + 1] = (phase.sin() * 100.0) as i8 as u8;
+        }
+    }
+
+    /// データなし（NaN）を比較できるよう置き換える値
+    const NO_DATA: f32 = -1.0;
+
+    fn data(line: &[f32]) -> Vec<f32> {
+        line.iter().map(|&v| if v.is_nan() { NO_DATA } else { v }).collect()
+    }
+
+    #[test]
+    fn test_block_frequency() {
+        let mut block = vec![0u8; 16];
+        assert_eq!(block_frequency(&block), None);
+        block[0] = 0x7F;
+        block[1] = 0x7F;
+        block[2..10].copy_from_slice(&2_400_000_000u64.to_le_bytes());
+        assert_eq!(block_frequency(&block), Some(2_400_000_000));
+        assert_eq!(block_frequency(&block[..9]), None);
+    }
+
+    #[test]
+    fn test_place() {
+        // 40 MHz 幅を 20 MHz/ブロック・FFT サイズ 8 で掃引すると 16 ビン
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 16).unwrap();
+        let spectrum: Vec<f32> = (0..8).map(|i| i as f32).collect();
+
+        assert!(!sweep.place(120_000_000, &spectrum));
+        assert_eq!(data(&sweep.line[8..]), [1.0, 2.0, 3.0, NO_DATA, 5.0, 6.0, 7.0, NO_DATA]);
+
+        // 末尾をはみ出す部分は捨てる
+        assert!(!sweep.place(135_000_000, &spectrum));
+        assert_eq!(&sweep.line[14..], &[1.0, 2.0]);
+
+        // 範囲外は無視する
+        let before = data(&sweep.line);
+        assert!(!sweep.place(99_000_000, &spectrum));
+        assert!(!sweep.place(141_000_000, &spectrum));
+        assert_eq!(data(&sweep.line), before);
+        assert_eq!(sweep.metrics().out_of_range_blocks, 2);
+
+        // 下端のブロックで掃引が完了する
+        assert!(sweep.place(100_000_000, &spectrum));
+        assert_eq!(sweep.sweep_count(), 1);
+        assert_eq!(data(sweep.completed()), before);
+        assert_eq!(data(&sweep.line[..8]), [1.0, 2.0, 3.0, NO_DATA, 5.0, 6.0, 7.0, NO_DATA]);
+        assert!(sweep.line[8..].iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn test_place_odd_fft_size() {
+        // n = 9: 1..4 (ceil(27/8)=4 を含む) と 5..8 (ceil(63/8)=8 を含む)
+        let mut sweep = SweepAssembler::new(0.0, 1e6, 32).unwrap();
+        let spectrum: Vec<f32> = (0..9).map(|i| i as f32).collect();
+        sweep.place(0, &spectrum);
+        assert_eq!(data(&sweep.line[..9]), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, NO_DATA]);
+    }
+
+    #[test]
+    fn test_push_transfer() {
+        let n = 64;
+        let mut fft = FFT::new(n, &vec![1.0; n]);
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 128).unwrap();
+
+        let mut transfer = Vec::new();
+        push_block(&mut transfer, 120_000_000, n, 20);
+        // ヘッダが壊れたブロックは読み捨てる
+        push_block(&mut transfer, 100_000_000, n, 0);
+        let broken = transfer.len() - BYTES_PER_BLOCK;
+        transfer[broken] = 0;
+        push_block(&mut transfer, 100_000_000, n, 20);
+
+        assert_eq!(sweep.push_transfer(&mut fft, &transfer).unwrap(), 1);
+        assert_eq!(sweep.sweep_count(), 1);
+        assert_eq!(sweep.metrics().invalid_blocks, 1);
+
+        // 正の周波数 +20 ビンのトーンは DC 中心配置で n/2 + 20 にあり、
+        // 正側の範囲（5n/8 から）の 12 ビン目となる
+        let mut line = vec![0.0f32; 128];
+        sweep.completed_line(&mut line).unwrap();
+        // 書き込まれていないビンは NaN なので、正側の範囲の中で探す
+        let pos2 = 64 + (n / 8 * 5 - n / 8);
+        let peak = (pos2..pos2 + 17).max_by(|&a, &b| line[a].total_cmp(&line[b])).unwrap();
+        assert_eq!(peak, pos2 + 12);
+        // 下端のブロックは次の掃引のラインに書き込まれる
+        assert!(line[..64].iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn test_push_transfer_full_block() {
+        // FFT サイズ 8192 ではブロック全体を使い、ヘッダ部分はサンプル 0 として FFT する
+        let n = crate::hackrf::SAMPLES_PER_BLOCK;
+        let mut fft = FFT::new(n, &vec![1.0; n]);
+        let mut sweep = SweepAssembler::new(100e6, 120e6, n).unwrap();
+        let mut transfer = Vec::new();
+        push_block(&mut transfer, 100_000_000, n, 20);
+        // トーンで上書きされたヘッダを書き直す
+        transfer[..2].fill(0x7F);
+        transfer[2..BLOCK_HEADER_LEN].copy_from_slice(&100_000_000u64.to_le_bytes());
+
+        assert_eq!(sweep.push_transfer(&mut fft, &transfer).unwrap(), 1);
+        // 転送バッファ自体は書き換えない
+        assert_eq!(block_frequency(&transfer), Some(100_000_000));
+
+        let mut payload = transfer[..BYTES_PER_BLOCK].to_vec();
+        payload[..BLOCK_HEADER_LEN].fill(0);
+        let mut expected = vec![0.0f32; n];
+        fft.fft(as_i8(&payload), &mut expected).unwrap();
+        assert_eq!(sweep.spectra, expected);
+    }
+
+    #[test]
+    fn test_multi_range() {
+        // 433, 868, 2400 MHz 付近の3範囲。フレームはセグメントを掃引の順に連結する
+        let mut sweep = SweepAssembler::with_ranges(&[420e6, 440e6, 860e6, 880e6, 2400e6, 2440e6], &[8, 8, 16]).unwrap();
+        assert_eq!(sweep.bin_count(), 32);
+        assert_eq!(sweep.segment_count(), 3);
+        assert_eq!(sweep.segment_offset(2), Some(16));
+        assert_eq!(sweep.segment_bin_count(1), Some(8));
+        assert_eq!(sweep.segment_low_freq(1), Some(860e6));
+        assert_eq!(sweep.segment_high_freq(3), None);
+        assert_eq!(sweep.segment_bin_width(3), None);
+
+        let mut frequencies = vec![0.0; 32];
+        sweep.frequencies(&mut frequencies).unwrap();
+        assert_eq!(frequencies[0], 420e6);
+        assert_eq!(frequencies[9], 862.5e6);
+        assert_eq!(frequencies[31], 2437.5e6);
+
+        let spectrum: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        // 2番目以降の範囲の下端では完了しない
+        assert!(!sweep.place(860_000_000, &spectrum));
+        assert!(!sweep.place(2_420_000_000, &spectrum));
+        // 範囲の間は無視する
+        assert!(!sweep.place(1_000_000_000, &spectrum));
+        // セグメントからはみ出す部分は隣のセグメントに書き込まない
+        assert_eq!(data(&sweep.line[8..16]), [1.0, 2.0, 3.0, NO_DATA, 5.0, 6.0, 7.0, NO_DATA]);
+        assert!(sweep.line[..8].iter().all(|v| v.is_nan()));
+        assert_eq!(data(&sweep.line[24..]), [1.0, 2.0, 3.0, NO_DATA, 5.0, 6.0, 7.0, NO_DATA]);
+
+        assert!(sweep.place(420_000_000, &spectrum));
+        assert_eq!(sweep.sweep_count(), 1);
+        assert_eq!(data(sweep.completed_segment(1)), [1.0, 2.0, 3.0, NO_DATA, 5.0, 6.0, 7.0, NO_DATA]);
+        assert!(sweep.completed_segment(0).iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn test_calibration() {
+        // 2つの範囲で、各セグメントの周波数軸の補正量を加える
+        let mut sweep = SweepAssembler::with_ranges(&[100e6, 120e6, 400e6, 420e6], &[8, 8]).unwrap();
+        let mut set = CalibrationSet::new();
+        set.insert(16, 20, false, &CalibrationTable::new(&[100e6, 400e6], &[1.0, 4.0]).unwrap());
+        set.insert(16, 20, true, &CalibrationTable::new(&[0.0], &[-10.0]).unwrap());
+        assert!(sweep.select_calibration(&set, 24, 20, false));
+        assert!(sweep.has_calibration());
+
+        let spectrum = [0.0f32; 8];
+        sweep.place(400_000_000, &spectrum);
+        sweep.place(100_000_000, &spectrum);
+        let completed = sweep.completed_segment(1);
+        assert!((completed[1] - 4.0).abs() < 1e-6, "{:?}", completed);
+        assert!(completed[3].is_nan());
+        // 完成したフレームは校正済みで、表示フレームにもそのまままとめられる
+        let mut frame = vec![0.0f32; 16];
+        assert_eq!(sweep.take_frame(&mut frame).unwrap(), 1);
+        assert!((frame[9] - 4.0).abs() < 1e-6);
+
+        // アンプの状態を変えると別のテーブル、使えるテーブルがなければ校正しない
+        assert!(sweep.select_calibration(&set, 16, 20, true));
+        sweep.place(100_000_000, &spectrum);
+        assert_eq!(sweep.completed_segment(0)[1], -10.0);
+        assert!(!sweep.select_calibration(&CalibrationSet::new(), 16, 20, true));
+        sweep.place(100_000_000, &spectrum);
+        assert_eq!(sweep.completed_segment(0)[1], 0.0);
+    }
+
+    #[test]
+    fn test_from_plan() {
+        let plan = SweepPlan::new(&[(2400, 2450), (433, 434)], 16384, 20_000_000, 7_500_000, SweepStyle::Interleaved).unwrap();
+        let rate = SampleRate::new(20e6).unwrap();
+        let sweep = SweepAssembler::from_plan(&plan, 256, &rate).unwrap();
+        let segments = sweep.segments();
+        assert_eq!(
+            segments,
+            &[
+                SweepSegment { low_freq_hz: 433e6, high_freq_hz: 453e6, bin_width_hz: 78125.0, offset: 0, bin_count: 256 },
+                SweepSegment { low_freq_hz: 2400e6, high_freq_hz: 2460e6, bin_width_hz: 78125.0, offset: 256, bin_count: 768 },
+            ]
+        );
+        assert_eq!(segments[1].bin_width_hz, rate.bin_width_hz(256));
+
+        // 1 MHz 単位でないレートでは、ビン幅は実際のレートに合わせる
+        let rate = SampleRate::new(10e6 / 3.0).unwrap();
+        let plan = SweepPlan::new(&[(100, 110)], 16384, 3_333_333, 1_250_000, SweepStyle::Interleaved).unwrap();
+        let sweep = SweepAssembler::from_plan(&plan, 256, &rate).unwrap();
+        let segment = sweep.segments()[0];
+        assert_eq!(segment.bin_count, 998);
+        assert_eq!(segment.bin_width_hz, rate.bin_width_hz(256));
+        assert_eq!(segment.high_freq_hz, 100e6 + 998.0 * rate.bin_width_hz(256));
+        // ビンの周波数も実際のビン幅の倍数
+        assert_eq!(segment.bin_frequency(997), 100e6 + 997.0 * rate.bin_width_hz(256));
+    }
+
+    #[test]
+    fn test_dropped_and_repeated_steps() {
+        // 100-160 MHz をインターリーブで掃引: 100, 105, 120, 125, 140, 145 MHz
+        let plan = SweepPlan::new(&[(100, 160)], 2 * BYTES_PER_BLOCK as u32, 20_000_000, 7_500_000, SweepStyle::Interleaved).unwrap();
+        let rate = SampleRate::new(20e6).unwrap();
+        let mut sweep = SweepAssembler::from_plan(&plan, 8, &rate).unwrap();
+        let spectrum = [0.0f32; 8];
+        let place = |sweep: &mut SweepAssembler, mhz: u64| {
+            // 1ステップ 2 ブロック
+            let a = sweep.place(mhz * 1_000_000, &spectrum);
+            let b = sweep.place(mhz * 1_000_000, &spectrum);
+            assert!(!b, "continuation block at {} MHz completed a sweep", mhz);
+            a
+        };
+
+        // 最初の掃引の開始までは照合しない
+        place(&mut sweep, 125);
+        assert!(place(&mut sweep, 100));
+        assert_eq!(sweep.completed_status(), SweepStatus::default());
+
+        // 120 MHz を取りこぼし、105 MHz を重複して受信
+        for mhz in [105, 125, 105, 140, 145] {
+            place(&mut sweep, mhz);
+        }
+        assert!(place(&mut sweep, 100));
+        assert_eq!(sweep.completed_status(), SweepStatus { missing_steps: 1, repeated_steps: 1 });
+
+        // 掃引の末尾の 140, 145 MHz を取りこぼす
+        for mhz in [105, 120, 125] {
+            place(&mut sweep, mhz);
+        }
+        assert!(place(&mut sweep, 100));
+        assert_eq!(sweep.completed_status(), SweepStatus { missing_steps: 2, repeated_steps: 0 });
+        // 取りこぼしたステップのビンはデータなし
+        let segment = sweep.segments()[0];
+        let pos = ((140e6 - segment.low_freq_hz) / segment.bin_width_hz) as usize;
+        assert!(sweep.completed()[pos + 1].is_nan());
+
+        // ステップの格子にない周波数
+        place(&mut sweep, 110);
+
+        assert_eq!(
+            sweep.metrics(),
+            SweepMetrics {
+                invalid_blocks: 0,
+                out_of_range_blocks: 0,
+                unexpected_blocks: 1,
+                dropped_steps: 3,
+                repeated_steps: 1,
+                overruns: 2,
+                incomplete_sweeps: 2,
+            }
+        );
+
+        sweep.reset();
+        assert_eq!(sweep.metrics(), SweepMetrics::default());
+        assert!(sweep.completed().iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn test_step_averaging() {
+        // 1ステップ 4 ブロックのブロックは平均する
+        let plan = SweepPlan::new(&[(100, 120)], 4 * BYTES_PER_BLOCK as u32, 20_000_000, 0, SweepStyle::Linear).unwrap();
+        let mut sweep = SweepAssembler::from_plan(&plan, 8, &SampleRate::new(20e6).unwrap()).unwrap();
+        for level in [-10.0, -20.0, -30.0, -40.0] {
+            sweep.place(100_000_000, &[level; 8]);
+        }
+        assert_eq!(sweep.line[1], -25.0);
+        // 次のステップ（この計画では次の掃引）の最初のブロックは上書きする
+        assert!(sweep.place(100_000_000, &[-50.0; 8]));
+        assert_eq!(sweep.line[1], -50.0);
+        assert_eq!(sweep.completed()[1], -25.0);
+    }
+
+    #[test]
+    fn test_track_steps() {
+        // wasm から使う場合はステップの順序を後から与える
+        let mut sweep = SweepAssembler::with_ranges(&[100e6, 140e6, 400e6, 420e6], &[16, 8]).unwrap();
+        sweep.track_steps(20_000_000, false, 1).unwrap();
+        assert_eq!(sweep.steps, vec![100_000_000, 120_000_000, 400_000_000]);
+
+        let spectrum = [0.0f32; 8];
+        for mhz in [100, 120, 400, 100, 400, 100] {
+            sweep.place(mhz * 1_000_000, &spectrum);
+        }
+        assert_eq!(sweep.completed_status(), SweepStatus { missing_steps: 1, repeated_steps: 0 });
+        assert_eq!(sweep.metrics().incomplete_sweeps, 1);
+
+        assert_eq!(sweep.track_steps(0, false, 1), Err(Error::ZeroParameter("Step width")));
+        assert_eq!(sweep.track_steps(1, false, 0), Err(Error::ZeroParameter("Blocks per step")));
+    }
+
+    #[test]
+    fn test_take_frame() {
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 16).unwrap();
+        let mut frame = vec![0.0f32; 16];
+        assert_eq!(sweep.take_frame(&mut frame), Ok(0));
+
+        // 3回の掃引のうち2回目だけにバーストがある
+        let quiet = [-80.0f32; 8];
+        let mut burst = quiet;
+        burst[2] = -20.0;
+        sweep.place(100_000_000, &quiet);
+        for spectrum in [&quiet, &burst, &quiet] {
+            sweep.place(120_000_000, spectrum);
+            sweep.place(100_000_000, &quiet);
+        }
+        assert_eq!(sweep.pending_sweeps(), 4);
+
+        // 最初の完了はデータのない掃引なので、ビンごとのデータ数で平均する
+        assert_eq!(sweep.take_frame(&mut frame), Ok(4));
+        assert_eq!(frame[8 + 1], -20.0);
+        assert_eq!(frame[8 + 2], -80.0);
+        assert_eq!(data(&frame[..8]), [-80.0, -80.0, -80.0, NO_DATA, -80.0, -80.0, -80.0, NO_DATA]);
+        assert_eq!(sweep.pending_sweeps(), 0);
+
+        sweep.set_frame_detector(FrameDetector::Mean);
+        for spectrum in [&quiet, &burst, &quiet] {
+            sweep.place(120_000_000, spectrum);
+            sweep.place(100_000_000, &quiet);
+        }
+        assert_eq!(sweep.take_frame(&mut frame), Ok(3));
+        assert_eq!(frame[8 + 1], -60.0);
+        assert_eq!(frame[8 + 2], -80.0);
+
+        assert_eq!(sweep.take_frame(&mut [0.0; 4]), Err(Error::OutputLengthMismatch { expected: 16, actual: 4 }));
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(
+            SweepAssembler::new(2e6, 1e6, 8).err(),
+            Some(Error::InvalidFrequencyRange { low: 2e6, high: 1e6 })
+        );
+        assert_eq!(SweepAssembler::new(1e6, 2e6, 0).err(), Some(Error::ZeroParameter("Bin count")));
+        assert_eq!(SweepAssembler::with_segments(&[]).err(), Some(Error::ZeroParameter("Range count")));
+        assert_eq!(
+            SweepAssembler::with_ranges(&[1e6, 2e6, 3e6], &[8, 8]).err(),
+            Some(Error::InputLengthMismatch { expected: 4, actual: 3 })
+        );
+        // 重なる範囲
+        assert_eq!(
+            SweepAssembler::with_ranges(&[1e6, 3e6, 2e6, 4e6], &[8, 8]).err(),
+            Some(Error::InvalidFrequencyRange { low: 2e6, high: 4e6 })
+        );
+
+        let mut sweep = SweepAssembler::new(1e6, 2e6, 8).unwrap();
+        let mut fft = FFT::new(16384, &vec![1.0; 16384]);
+        assert!(matches!(
+            sweep.push_transfer(&mut fft, &[0u8; BYTES_PER_BLOCK]),
+            Err(Error::PayloadOutOfBlock { .. })
+        ));
+        assert_eq!(sweep.completed_line(&mut [0.0; 4]), Err(Error::OutputLengthMismatch { expected: 8, actual: 4 }));
+    }
+}
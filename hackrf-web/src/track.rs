@@ -0,0 +1,511 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::sweep::check_segments;
+use crate::{Error, SweepAssembler, SweepSegment};
+
+/// 1回の掃引で検出した信号
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    /// 信号の周波数範囲 [Hz]
+    pub low_freq_hz: f64,
+    pub high_freq_hz: f64,
+    /// 中心周波数 [Hz]
+    pub center_freq_hz: f64,
+    /// 最大値 [dB]
+    pub peak_db: f32,
+}
+
+#[wasm_bindgen]
+impl Detection {
+    #[wasm_bindgen(constructor)]
+    pub fn new(low_freq_hz: f64, high_freq_hz: f64, center_freq_hz: f64, peak_db: f32) -> Detection {
+        Detection { low_freq_hz, high_freq_hz, center_freq_hz, peak_db }
+    }
+}
+
+/// トラックの開始・終了
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrackEventKind {
+    /// 新しい信号を検出した
+    #[default]
+    Start = 0,
+    /// `max_missed_sweeps` を超えて検出されなかった
+    End = 1,
+}
+
+/// トラックの現在の状態
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackSummary {
+    /// 掃引をまたいで同じ信号に付く番号
+    pub id: u32,
+    pub first_seen_s: f64,
+    pub last_seen_s: f64,
+    /// 最後に検出したときの周波数範囲 [Hz]
+    pub low_freq_hz: f64,
+    pub high_freq_hz: f64,
+    /// 最後に検出したときの中心周波数 [Hz]
+    pub center_freq_hz: f64,
+    /// 最初に検出したときからの中心周波数の変化 [Hz]
+    pub drift_hz: f64,
+    /// 中心周波数の変化の絶対値の最大 [Hz]
+    pub max_drift_hz: f64,
+    /// 最初に検出してからの掃引のうち、検出された割合
+    pub duty_cycle: f32,
+    /// 最後に検出したときの最大値 [dB]
+    pub peak_db: f32,
+    /// トラック全体での最大値 [dB]
+    pub max_peak_db: f32,
+    /// 直前の掃引で検出されなかった回数
+    pub missed_sweeps: u32,
+}
+
+/// トラックの開始・終了イベント
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackEvent {
+    pub kind: TrackEventKind,
+    /// イベントが発生した掃引の時刻 [s]
+    pub timestamp_s: f64,
+    pub track: TrackSummary,
+}
+
+#[derive(Clone, Debug)]
+struct Track {
+    id: u32,
+    first_seen_s: f64,
+    last_seen_s: f64,
+    low_freq_hz: f64,
+    high_freq_hz: f64,
+    center_freq_hz: f64,
+    first_center_freq_hz: f64,
+    max_drift_hz: f64,
+    peak_db: f32,
+    max_peak_db: f32,
+    seen_sweeps: u32,
+    total_sweeps: u32,
+    missed_sweeps: u32,
+    /// 掃引ごとの最大値 [dB]。検出されなかった掃引は NaN
+    history: VecDeque<f32>,
+}
+
+impl Track {
+    fn summary(&self) -> TrackSummary {
+        TrackSummary {
+            id: self.id,
+            first_seen_s: self.first_seen_s,
+            last_seen_s: self.last_seen_s,
+            low_freq_hz: self.low_freq_hz,
+            high_freq_hz: self.high_freq_hz,
+            center_freq_hz: self.center_freq_hz,
+            drift_hz: self.center_freq_hz - self.first_center_freq_hz,
+            max_drift_hz: self.max_drift_hz,
+            duty_cycle: self.seen_sweeps as f32 / self.total_sweeps as f32,
+            peak_db: self.peak_db,
+            max_peak_db: self.max_peak_db,
+            missed_sweeps: self.missed_sweeps,
+        }
+    }
+
+    /// 周波数範囲が `detection` と重なる幅 [Hz]。重ならなければ `None`
+    fn overlap(&self, detection: &Detection, tolerance_hz: f64) -> Option<f64> {
+        let low = detection.low_freq_hz.max(self.low_freq_hz - tolerance_hz);
+        let high = detection.high_freq_hz.min(self.high_freq_hz + tolerance_hz);
+        (low <= high).then_some(high - low)
+    }
+
+    fn push_history(&mut self, peak_db: f32, history_len: usize) {
+        if self.history.len() == history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back(peak_db);
+    }
+}
+
+/// 掃引ごとの検出を周波数範囲の重なりでトラックにまとめ、信号を時間方向に追跡する。
+///
+/// 検出は最大値の大きい順に、重なりの最も大きいトラックに割り当てる（1つのトラックには1つの検出）。
+/// どのトラックとも重ならない検出は新しいトラックとして開始イベントを出し、
+/// `max_missed_sweeps` 回を超えて続けて検出されなかったトラックは終了イベントを出して削除する。
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct EmissionTracker {
+    max_missed_sweeps: u32,
+    history_len: usize,
+    frequency_tolerance_hz: f64,
+    tracks: Vec<Track>,
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl EmissionTracker {
+    /// 新しいトラッカーを作成する。
+    ///
+    /// # 引数
+    /// * `max_missed_sweeps` - トラックを終了せずに待つ、検出されない掃引の数（間欠的な信号用）
+    /// * `history_len` - トラックごとに保持する最大値の履歴の長さ [掃引]
+    /// * `frequency_tolerance_hz` - 重なりの判定でトラックの周波数範囲を両側に広げる幅 [Hz]
+    ///
+    /// # エラー
+    /// * `Error::ZeroHistoryLength` - `history_len` が 0 の場合
+    /// * `Error::InvalidParameter` - `frequency_tolerance_hz` が 0 以上の有限値でない場合
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_missed_sweeps: u32, history_len: usize, frequency_tolerance_hz: f64) -> Result<EmissionTracker, Error> {
+        if history_len == 0 {
+            return Err(Error::ZeroHistoryLength);
+        }
+        if !(frequency_tolerance_hz.is_finite() && frequency_tolerance_hz >= 0.0) {
+            return Err(Error::InvalidParameter { name: "Frequency tolerance", value: frequency_tolerance_hz });
+        }
+        Ok(EmissionTracker { max_missed_sweeps, history_len, frequency_tolerance_hz, tracks: Vec::new(), next_id: 0 })
+    }
+
+    pub fn max_missed_sweeps(&self) -> u32 {
+        self.max_missed_sweeps
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history_len
+    }
+
+    pub fn frequency_tolerance_hz(&self) -> f64 {
+        self.frequency_tolerance_hz
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// 全てのトラックの状態（開始した順）
+    pub fn tracks(&self) -> Vec<TrackSummary> {
+        self.tracks.iter().map(Track::summary).collect()
+    }
+
+    /// トラック `id` の最大値の履歴 [dB]（古い順、検出されなかった掃引は NaN）
+    pub fn peak_history(&self, id: u32) -> Option<Vec<f32>> {
+        self.tracks.iter().find(|t| t.id == id).map(|t| t.history.iter().copied().collect())
+    }
+
+    /// 全てのトラックを破棄する。終了イベントは出さない。
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+    }
+
+    /// 時刻 `timestamp_s` [s] の掃引の検出でトラックを更新する。
+    ///
+    /// # 戻り値
+    /// この掃引で発生した開始・終了イベント
+    ///
+    /// # エラー
+    /// * `Error::InvalidFrequencyRange` - 周波数範囲が下端 < 上端 を満たさない検出がある場合
+    pub fn update(&mut self, timestamp_s: f64, detections: Vec<Detection>) -> Result<Vec<TrackEvent>, Error> {
+        self.update_detections(timestamp_s, &detections)
+    }
+
+    /// 掃引アセンブラの直前に完成したフレームから `threshold_db` を超える信号を検出し
+    /// （`find_detections`）、トラックを更新する。
+    pub fn update_sweep(&mut self, timestamp_s: f64, sweep: &SweepAssembler, threshold_db: f32) -> Result<Vec<TrackEvent>, Error> {
+        let detections = find_detections(sweep.segments(), sweep.completed(), threshold_db)?;
+        self.update_detections(timestamp_s, &detections)
+    }
+}
+
+impl EmissionTracker {
+    /// `update` と同じ（スライスを受け取る）。
+    pub fn update_detections(&mut self, timestamp_s: f64, detections: &[Detection]) -> Result<Vec<TrackEvent>, Error> {
+        if let Some(d) = detections.iter().find(|d| d.low_freq_hz.is_nan() || d.high_freq_hz.is_nan() || d.low_freq_hz >= d.high_freq_hz) {
+            return Err(Error::InvalidFrequencyRange { low: d.low_freq_hz, high: d.high_freq_hz });
+        }
+
+        let mut order: Vec<usize> = (0..detections.len()).collect();
+        order.sort_by(|&a, &b| detections[b].peak_db.total_cmp(&detections[a].peak_db));
+
+        let mut matched = vec![false; self.tracks.len()];
+        let mut events = Vec::new();
+        let mut new_tracks = Vec::new();
+        for i in order {
+            let detection = &detections[i];
+            let best = (0..self.tracks.len())
+                .filter(|&t| !matched[t])
+                .filter_map(|t| self.tracks[t].overlap(detection, self.frequency_tolerance_hz).map(|o| (t, o)))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            match best {
+                Some((t, _)) => {
+                    matched[t] = true;
+                    let track = &mut self.tracks[t];
+                    track.last_seen_s = timestamp_s;
+                    track.low_freq_hz = detection.low_freq_hz;
+                    track.high_freq_hz = detection.high_freq_hz;
+                    track.center_freq_hz = detection.center_freq_hz;
+                    track.max_drift_hz = track.max_drift_hz.max((detection.center_freq_hz - track.first_center_freq_hz).abs());
+                    track.peak_db = detection.peak_db;
+                    track.max_peak_db = track.max_peak_db.max(detection.peak_db);
+                    track.seen_sweeps += 1;
+                    track.total_sweeps += 1;
+                    track.missed_sweeps = 0;
+                    track.push_history(detection.peak_db, self.history_len);
+                }
+                None => {
+                    let mut track = Track {
+                        id: self.next_id,
+                        first_seen_s: timestamp_s,
+                        last_seen_s: timestamp_s,
+                        low_freq_hz: detection.low_freq_hz,
+                        high_freq_hz: detection.high_freq_hz,
+                        center_freq_hz: detection.center_freq_hz,
+                        first_center_freq_hz: detection.center_freq_hz,
+                        max_drift_hz: 0.0,
+                        peak_db: detection.peak_db,
+                        max_peak_db: detection.peak_db,
+                        seen_sweeps: 1,
+                        total_sweeps: 1,
+                        missed_sweeps: 0,
+                        history: VecDeque::with_capacity(self.history_len),
+                    };
+                    track.push_history(detection.peak_db, self.history_len);
+                    self.next_id = self.next_id.wrapping_add(1);
+                    events.push(TrackEvent { kind: TrackEventKind::Start, timestamp_s, track: track.summary() });
+                    new_tracks.push(track);
+                }
+            }
+        }
+
+        let max_missed_sweeps = self.max_missed_sweeps;
+        let history_len = self.history_len;
+        let mut index = 0;
+        self.tracks.retain_mut(|track| {
+            let seen = matched[index];
+            index += 1;
+            if seen {
+                return true;
+            }
+            track.missed_sweeps += 1;
+            track.total_sweeps += 1;
+            track.push_history(f32::NAN, history_len);
+            if track.missed_sweeps > max_missed_sweeps {
+                events.push(TrackEvent { kind: TrackEventKind::End, timestamp_s, track: track.summary() });
+                return false;
+            }
+            true
+        });
+        self.tracks.extend(new_tracks);
+        Ok(events)
+    }
+}
+
+/// フレーム `line` から、値が `threshold_db` を超えるビンが連続する範囲を信号として取り出す。
+///
+/// 範囲はセグメントの境界で分ける。周波数範囲はビンの下端から上端まで、
+/// 中心周波数は各ビンの中心を電力（10^(v / 5)）で重み付けした平均とする。NaN のビンは超えないものとして扱う。
+///
+/// # エラー
+/// * `Error::InvalidParameter` - セグメントの `offset` がフレームを先頭から隙間なく並んでいない場合
+/// * `Error::InputLengthMismatch` - `line.len()` がセグメントのビン数の合計と一致しない場合
+pub fn find_detections(segments: &[SweepSegment], line: &[f32], threshold_db: f32) -> Result<Vec<Detection>, Error> {
+    check_segments(segments, line.len())?;
+
+    let mut detections = Vec::new();
+    for segment in segments {
+        let values = &line[segment.offset..segment.offset + segment.bin_count];
+        let width = segment.bin_width_hz;
+        let mut bin = 0;
+        while bin < values.len() {
+            if values[bin].is_nan() || values[bin] <= threshold_db {
+                bin += 1;
+                continue;
+            }
+            let low_bin = bin;
+            let mut peak_db = f32::NEG_INFINITY;
+            let (mut power, mut weighted) = (0.0f64, 0.0f64);
+            while bin < values.len() && values[bin] > threshold_db {
+                let p = 10f64.powf(values[bin] as f64 / 5.0);
+                power += p;
+                weighted += p * (segment.bin_frequency(bin) + width / 2.0);
+                peak_db = peak_db.max(values[bin]);
+                bin += 1;
+            }
+            detections.push(Detection {
+                low_freq_hz: segment.bin_frequency(low_bin),
+                high_freq_hz: segment.bin_frequency(bin),
+                center_freq_hz: weighted / power,
+                peak_db,
+            });
+        }
+    }
+    Ok(detections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(center_mhz: f64, width_mhz: f64, peak_db: f32) -> Detection {
+        let center = center_mhz * 1e6;
+        let half = width_mhz * 1e6 / 2.0;
+        Detection::new(center - half, center + half, center, peak_db)
+    }
+
+    #[test]
+    fn test_start_and_end() {
+        let mut tracker = EmissionTracker::new(1, 8, 0.0).unwrap();
+        let events = tracker.update(0.0, vec![detection(100.0, 0.2, -40.0), detection(433.9, 0.1, -50.0)]).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.kind == TrackEventKind::Start && e.timestamp_s == 0.0));
+        // 最大値の大きい順に番号が付く
+        assert_eq!((events[0].track.id, events[0].track.center_freq_hz), (0, 100e6));
+        assert_eq!((events[1].track.id, events[1].track.center_freq_hz), (1, 433.9e6));
+
+        assert!(tracker.update(1.0, vec![detection(100.0, 0.2, -42.0), detection(433.9, 0.1, -50.0)]).unwrap().is_empty());
+        // 1回検出されなくても続く
+        assert!(tracker.update(2.0, vec![detection(100.0, 0.2, -41.0)]).unwrap().is_empty());
+        assert_eq!(tracker.track_count(), 2);
+        let events = tracker.update(3.0, vec![detection(100.0, 0.2, -41.0)]).unwrap();
+        assert_eq!(events.len(), 1);
+        let end = events[0];
+        assert_eq!((end.kind, end.timestamp_s), (TrackEventKind::End, 3.0));
+        assert_eq!(end.track.id, 1);
+        assert_eq!((end.track.first_seen_s, end.track.last_seen_s), (0.0, 1.0));
+        assert_eq!(end.track.missed_sweeps, 2);
+
+        let track = tracker.tracks()[0];
+        assert_eq!((track.id, track.last_seen_s), (0, 3.0));
+        assert_eq!((track.peak_db, track.max_peak_db), (-41.0, -40.0));
+        assert_eq!(track.duty_cycle, 1.0);
+        assert_eq!(tracker.peak_history(0), Some(vec![-40.0, -42.0, -41.0, -41.0]));
+        assert_eq!(tracker.peak_history(1), None);
+
+        // 終了したトラックの番号は再利用しない
+        let events = tracker.update(4.0, vec![detection(100.0, 0.2, -41.0), detection(433.9, 0.1, -50.0)]).unwrap();
+        assert_eq!(events[0].track.id, 2);
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut tracker = EmissionTracker::new(0, 8, 0.0).unwrap();
+        tracker.update(0.0, vec![detection(100.00, 0.1, -40.0)]).unwrap();
+        tracker.update(1.0, vec![detection(100.04, 0.1, -40.0)]).unwrap();
+        tracker.update(2.0, vec![detection(100.02, 0.1, -40.0)]).unwrap();
+        let track = tracker.tracks()[0];
+        assert_eq!(tracker.track_count(), 1);
+        assert!((track.drift_hz - 20e3).abs() < 1e-3);
+        assert!((track.max_drift_hz - 40e3).abs() < 1e-3);
+
+        // 重ならないほど動いたら別の信号
+        let events = tracker.update(3.0, vec![detection(100.2, 0.1, -40.0)]).unwrap();
+        assert_eq!(events.iter().map(|e| e.kind).collect::<Vec<_>>(), [TrackEventKind::Start, TrackEventKind::End]);
+
+        // 許容幅を広げると同じトラックのまま
+        let mut tracker = EmissionTracker::new(0, 8, 200e3).unwrap();
+        tracker.update(0.0, vec![detection(100.0, 0.1, -40.0)]).unwrap();
+        assert!(tracker.update(1.0, vec![detection(100.2, 0.1, -40.0)]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_duty_cycle_and_history() {
+        let mut tracker = EmissionTracker::new(2, 3, 0.0).unwrap();
+        for (i, seen) in [true, false, true, false, false].into_iter().enumerate() {
+            let detections = if seen { vec![detection(915.0, 0.5, -30.0 - i as f32)] } else { vec![] };
+            assert!(tracker.update(i as f64, detections).unwrap().iter().all(|e| e.kind == TrackEventKind::Start));
+        }
+        let track = tracker.tracks()[0];
+        assert_eq!(track.duty_cycle, 0.4);
+        assert_eq!(track.missed_sweeps, 2);
+        // 履歴は最新の 3 掃引
+        let history = tracker.peak_history(0).unwrap();
+        assert_eq!(history[0], -32.0);
+        assert!(history[1].is_nan() && history[2].is_nan());
+    }
+
+    #[test]
+    fn test_split_detection() {
+        let mut tracker = EmissionTracker::new(0, 4, 0.0).unwrap();
+        tracker.update(0.0, vec![detection(100.0, 1.0, -40.0)]).unwrap();
+        // 2つに分かれたら、強いほうがトラックを引き継ぐ
+        let events = tracker.update(1.0, vec![detection(99.7, 0.2, -60.0), detection(100.3, 0.2, -45.0)]).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].track.id, events[0].track.center_freq_hz), (1, 99.7e6));
+        assert_eq!(tracker.tracks()[0].center_freq_hz, 100.3e6);
+    }
+
+    #[test]
+    fn test_find_detections() {
+        let segments = [
+            SweepSegment { low_freq_hz: 100e6, high_freq_hz: 110e6, bin_width_hz: 1e6, offset: 0, bin_count: 10 },
+            SweepSegment { low_freq_hz: 200e6, high_freq_hz: 210e6, bin_width_hz: 1e6, offset: 10, bin_count: 10 },
+        ];
+        let mut line = [-90.0f32; 20];
+        line[3] = -40.0;
+        line[4] = -40.0;
+        line[9] = -50.0;
+        line[10] = -50.0;
+        line[15] = f32::NAN;
+
+        let detections = find_detections(&segments, &line, -60.0).unwrap();
+        assert_eq!(
+            detections,
+            [
+                Detection::new(103e6, 105e6, 104e6, -40.0),
+                // セグメントの境界で分ける
+                Detection::new(109e6, 110e6, 109.5e6, -50.0),
+                Detection::new(200e6, 201e6, 200.5e6, -50.0),
+            ]
+        );
+
+        // 中心は電力で重み付けする（10 dB 差は電力で 100 倍）
+        line[4] = -50.0;
+        let center = find_detections(&segments, &line, -60.0).unwrap()[0].center_freq_hz;
+        assert!((center - (103.5e6 * 100.0 + 104.5e6) / 101.0).abs() < 1.0);
+
+        assert_eq!(find_detections(&segments, &line[..10], -60.0), Err(Error::InputLengthMismatch { expected: 20, actual: 10 }));
+
+        // ビン数の合計が合っていても、offset がずれていればパニックせずエラーにする
+        let mut shifted = segments;
+        shifted[1].offset = 15;
+        assert_eq!(
+            find_detections(&shifted, &line, -60.0),
+            Err(Error::InvalidParameter { name: "Segment offset", value: 15.0 })
+        );
+        let mut overlapped = segments;
+        overlapped[1].offset = 5;
+        overlapped[1].bin_count = 15;
+        assert_eq!(
+            find_detections(&overlapped, &line, -60.0),
+            Err(Error::InvalidParameter { name: "Segment offset", value: 5.0 })
+        );
+    }
+
+    #[test]
+    fn test_update_sweep() {
+        let mut sweep = SweepAssembler::new(100e6, 140e6, 16).unwrap();
+        let mut spectrum = [-90.0f32; 8];
+        spectrum[2] = -30.0;
+        sweep.place(100_000_000, &[-90.0; 8]);
+        sweep.place(120_000_000, &spectrum);
+        sweep.place(100_000_000, &[-90.0; 8]);
+
+        let mut tracker = EmissionTracker::new(0, 4, 0.0).unwrap();
+        let events = tracker.update_sweep(0.0, &sweep, -60.0).unwrap();
+        assert_eq!(events.len(), 1);
+        // ビン 9（122.5..125 MHz）
+        assert_eq!((events[0].track.low_freq_hz, events[0].track.high_freq_hz), (122.5e6, 125e6));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(EmissionTracker::new(0, 0, 0.0).err(), Some(Error::ZeroHistoryLength));
+        assert_eq!(
+            EmissionTracker::new(0, 1, -1.0).err(),
+            Some(Error::InvalidParameter { name: "Frequency tolerance", value: -1.0 })
+        );
+        let mut tracker = EmissionTracker::new(0, 1, 0.0).unwrap();
+        assert_eq!(
+            tracker.update(0.0, vec![Detection::new(2.0, 1.0, 1.5, 0.0)]),
+            Err(Error::InvalidFrequencyRange { low: 2.0, high: 1.0 })
+        );
+        assert_eq!(tracker.track_count(), 0);
+    }
+}
@@ -44,6 +44,14 @@ async function test() {
 	console.log('Output values:', Array.from(output));
 	console.log('✓ DC component validation passed');
 
+	// 不正な引数は例外（Error オブジェクト）として通知される
+	assert.throws(
+		() => FFT.try_new(12, new Float32Array(8)),
+		(e) => e instanceof Error && /Window size must match FFT size/.test(e.message),
+		'try_new should throw an Error for a window length mismatch'
+	);
+	console.log('✓ try_new error validation passed');
+
 	console.log('\n✅ All tests passed!');
 }
 
@@ -0,0 +1,69 @@
+import assert from 'assert';
+import * as wasm from './node/hackrf_web.js';
+import { Backend } from '../backend.js';
+
+// worker.js と同じ Backend を模擬デバイスで動かし、open → plan → start → フレームの受信 → stopRx を通して確認する
+async function test() {
+	const carrier = 2440e6;
+	const backend = new Backend();
+	await backend.init(wasm);
+	assert.strictEqual(await backend.openSimulator({ noiseDb: -70, carriers: [{ frequencyHz: carrier, levelDb: -50 }] }), true);
+	console.log('✓ simulator opened');
+
+	const info = await backend.info();
+	assert.strictEqual(info.boardId, 2);
+	assert.deepStrictEqual(info.apiVersion, [1, 0, 7]);
+	console.log('✓ info');
+
+	await backend.setLnaGain(16);
+	await backend.setVgaGain(20);
+
+	const plan = await backend.plan({ ranges: [[2400, 2480]], fftSize: 256, sampleRate: 20e6 });
+	assert.strictEqual(plan.FFT_SIZE, 256);
+	assert.strictEqual(plan.lowFreq, 2400);
+	assert.strictEqual(plan.highFreq, 2480);
+	console.log('✓ plan', plan.freqBinCount, 'bins');
+
+	const columns = 160;
+	const frames = [];
+	await new Promise((resolve, reject) => {
+		backend.start({ columns, detector: 'max' }, (display, metrics) => {
+			frames.push({ display: Float32Array.from(display), metrics });
+			if (frames.length === 3) {
+				resolve();
+			}
+		}).catch(reject);
+	});
+	await backend.stopRx();
+	console.log('✓ received', frames.length, 'frames');
+
+	const { display, metrics } = frames[frames.length - 1];
+	assert.strictEqual(display.length, columns);
+	assert.ok(display.every(Number.isFinite), 'every column should have data');
+	assert.ok(metrics.sweepsPerFrame >= 1);
+	assert.strictEqual(metrics.sweep.droppedSteps, 0);
+	assert.strictEqual(metrics.sweep.invalidBlocks + metrics.sweep.outOfRangeBlocks + metrics.sweep.unexpectedBlocks, 0);
+
+	// 搬送波は 2440 MHz（表示の中央の列）に出る
+	const peak = display.indexOf(Math.max(...display));
+	const expected = Math.floor((carrier / 1e6 - plan.lowFreq) / plan.bandwidth * columns);
+	assert.ok(Math.abs(peak - expected) <= 1, `peak at column ${peak}, expected ${expected}`);
+
+	// dBm への換算: 総ゲイン 36 dB を引くので、搬送波はノイズフロアより十分高く、AutoRange の表示範囲に入る
+	const sorted = Array.from(display).sort((a, b) => a - b);
+	const median = sorted[columns >> 1];
+	assert.ok(display[peak] - median > 10, `peak ${display[peak]} dBm, median ${median} dBm`);
+	assert.ok(display[peak] <= metrics.referenceLevel, 'peak should be below the reference level');
+	assert.ok(median >= metrics.referenceLevel - metrics.span, 'noise floor should be above the bottom of the range');
+	console.log('✓ carrier found at', peak, 'column', display[peak].toFixed(1), 'dBm');
+
+	await backend.close();
+	console.log('✓ closed');
+
+	console.log('\n✅ All worker tests passed!');
+}
+
+test().catch(err => {
+	console.error('❌ Test failed:', err);
+	process.exit(1);
+});
@@ -25,6 +25,29 @@ import { Waterfall, WaterfallGL } from "./utils.js";
 
 const Backend = Comlink.wrap(new Worker("./worker.js", { type: "module" }));
 
+// simulate で開く模擬デバイスの電波環境（レベルは総ゲイン 0 dB のときの dBFS）
+const SIMULATOR_SCENE = {
+	noiseDb: -70,
+	carriers: [
+		{ frequencyHz: 433.92e6, levelDb: -50 },
+		{ frequencyHz: 868.3e6, levelDb: -55 },
+		{ frequencyHz: 2412e6, levelDb: -45 },
+		{ frequencyHz: 2437e6, levelDb: -50 },
+		{ frequencyHz: 2462e6, levelDb: -60 },
+	],
+};
+
+// "433-435, 863-870" のような追加の掃引範囲 [MHz] を [[433, 435], [863, 870]] にする
+function parseRanges(text) {
+	return text.split(',').map((s) => s.trim()).filter((s) => s).map((s) => {
+		const m = s.match(/^(\d+)\s*-\s*(\d+)$/);
+		if (!m) {
+			throw new Error(`invalid range: ${s}`);
+		}
+		return [+m[1], +m[2]];
+	});
+}
+
 createApp({
 	data() {
 		return {
@@ -43,13 +66,29 @@ createApp({
 			range: {
 				start: 2400,
 				stop: 2500,
+				// 同じ掃引で受信する追加の範囲（"433-435, 863-870" [MHz]）
+				extra: "",
 				fftSize: 256
 			},
+			// 直前に開始した掃引の周波数軸（セグメントごと）。掃引していない間は range から求める
+			axis: {
+				binCount: 0,
+				segments: [],
+			},
 			options: {
 				ampEnabled: false,
 				antennaEnabled: false,
 				lnaGain: 16,
 				vgaGain: 16,
+				// 表示の1フレームにまとめる掃引の検波方式 ('max' / 'mean')
+				frameDetector: 'max',
+				// 全ゲイン 0 dB のときの 0 dBFS 相当の入力電力 [dBm]（既知の信号で合わせる）
+				referenceOffset: 0,
+				// 表示範囲を直近のフレームから自動で決める（AutoRange）
+				autoRange: true,
+				// 表示上端のレベル [dBm] と表示範囲の幅 [dB]
+				referenceLevel: -30,
+				span: 100,
 				peakHold: false
 			},
 			info: {
@@ -62,6 +101,12 @@ createApp({
 			metrics: {
 				sweepPerSec: 0,
 				bytesPerSec: 0,
+				// 1フレームにまとめた掃引の数
+				sweepsPerFrame: 0,
+				// 校正テーブルを適用しているか
+				calibrated: false,
+				// SweepAssembler の SweepMetrics（掃引中のみ）
+				sweep: null,
 			},
 
 			currentHover: "",
@@ -72,6 +117,7 @@ createApp({
 					presets: [
 						{ name: "ISM 2.4GHz (Wi-Fi/BLE/Zigbee)", start: 2400, stop: 2485 },
 						{ name: "Wi-Fi 5GHz", start: 5150, stop: 5850 },
+						{ name: "ISM 433/868MHz + 2.4GHz", start: 2400, stop: 2485, extra: "433-435, 863-870" },
 					]
 				},
 				{
@@ -141,13 +187,30 @@ createApp({
 				if (preset) {
 					this.range.start = preset.start;
 					this.range.stop = preset.stop;
-					// FFTサイズは最大値に設定（startメソッド側で画面サイズに応じて制限される）
+					this.range.extra = preset.extra || "";
+					// FFTサイズは最大値に設定（表示は描画幅の列数にまとめる）
 					this.range.fftSize = 8192;
 					this.resetPeak();
 				}
 			}
 		},
 
+		// ゲイン設定ごとの校正テーブルを読み込む。
+		// [{ lnaGain, vgaGain, ampEnable, frequencies: [Hz...], corrections: [dB...] }, ...] の JSON
+		loadCalibration: async function (e) {
+			const file = e.target.files[0];
+			if (!file) return;
+			try {
+				const count = await this.backend.loadCalibration(JSON.parse(await file.text()));
+				this.snackbar.show = true;
+				this.snackbar.message = `loaded ${count} calibration tables`;
+			} catch (e) {
+				this.alert.title = "Error";
+				this.alert.content = e.message || e.toString();
+				this.alert.show = true;
+			}
+		},
+
 		resetPeak: function () {
 			this.maxData = null;
 		},
@@ -188,6 +251,17 @@ createApp({
 				}
 			}
 
+			await this.setupDevice();
+		},
+
+		// HackRF なしで試せるように、模擬デバイス（hackrf-web の SimulatedHackRF）を開く
+		simulate: async function () {
+			await this.backend.openSimulator(SIMULATOR_SCENE);
+			await this.setupDevice();
+		},
+
+		// 開いたデバイスの情報を表示し、現在のゲイン設定を適用する
+		setupDevice: async function () {
 			this.connected = true;
 			const { boardId, versionString, apiVersion, partId, serialNo } = await this.backend.info();
 
@@ -217,49 +291,49 @@ createApp({
 
 			const { canvasFft, canvasWf } = this;
 
-			const SAMPLE_RATE = 20e6;
-
-			const lowFreq = +this.range.start;
-			const highFreq0 = +this.range.stop;
-			const bandwidth0 = highFreq0 - lowFreq;
-			const steps = Math.ceil((bandwidth0 * 1e6) / SAMPLE_RATE);
-			const bandwidth = (steps * SAMPLE_RATE) / 1e6;
-			const highFreq = lowFreq + bandwidth;
-			this.range.stop = highFreq;
-
-			// const FFT_SIZE = +this.range.fftSize;
-			// const freqBinCount = (bandwidth*1e6) / SAMPLE_RATE * FFT_SIZE;
-			//
-			const freqBinCount0 = canvasFft.offsetWidth * window.devicePixelRatio;
-			const fftSize0 = Math.pow(2, Math.ceil(Math.log2((freqBinCount0 * SAMPLE_RATE) / (bandwidth * 1e6))));
-			const fftSize1 = fftSize0 < +this.range.fftSize ? fftSize0 : +this.range.fftSize;
-			const FFT_SIZE = fftSize1 > 8 ? fftSize1 : 8;
-			const freqBinCount = (bandwidth * 1e6) / SAMPLE_RATE * FFT_SIZE;
-
-			if (this.range.fftSize != FFT_SIZE) {
-				this.snackbar.show = true;
-				this.snackbar.message = "FFT Size is limited to rendering width";
-				this.range.fftSize = FFT_SIZE;
+			// 帯域とビン数は MeasurementPlan で決める。FFT サイズは指定どおりで、
+			// ビンは worker で描画幅の列数にまとめてから受け取る
+			let opts;
+			try {
+				const ranges = [[+this.range.start, +this.range.stop], ...parseRanges(this.range.extra)];
+				opts = await this.backend.plan({
+					ranges,
+					fftSize: +this.range.fftSize,
+					sampleRate: 20e6,
+				});
+				if (ranges.length === 1) {
+					this.range.stop = opts.highFreq;
+				}
+			} catch (e) {
+				this.alert.title = "Error";
+				this.alert.content = e.message || e.toString();
+				this.alert.show = true;
+				return;
 			}
+			const { lowFreq, highFreq, bandwidth, freqBinCount, segments } = opts;
 
-
-			console.log({ lowFreq, highFreq, bandwidth, freqBinCount });
-			const nx = Math.pow(2, Math.ceil(Math.log2(freqBinCount)));
+			const columns = Math.round(canvasFft.offsetWidth * window.devicePixelRatio);
+			console.log({ lowFreq, highFreq, bandwidth, freqBinCount, columns });
+			const nx = Math.pow(2, Math.ceil(Math.log2(columns)));
 			const maxTextureSize = 16384;
 			const useWebGL = nx <= maxTextureSize;
 			console.log(`Waterfall: ${useWebGL ? 'WebGL (WaterfallGL)' : 'Canvas 2D (Waterfall)'} - nx=${nx}, maxTextureSize=${maxTextureSize}`);
 			const waterfall = useWebGL ?
-				new WaterfallGL(canvasWf, freqBinCount, 256) :
-				new Waterfall(canvasWf, freqBinCount, 256);
+				new WaterfallGL(canvasWf, columns, 256) :
+				new Waterfall(canvasWf, columns, 256);
 
 			canvasFft.height = 200;
-			canvasFft.width = freqBinCount;
+			canvasFft.width = columns;
 
 			const ctxFft = canvasFft.getContext('2d');
 
 			this.maxData = null;
-			await this.backend.start({ FFT_SIZE, SAMPLE_RATE, lowFreq, highFreq, bandwidth, freqBinCount }, Comlink.proxy((data, metrics) => {
+			await this.backend.start({ columns, detector: this.options.frameDetector }, Comlink.proxy((data, metrics) => {
 				this.metrics = metrics;
+				if (this.options.autoRange) {
+					this.options.referenceLevel = Math.round(metrics.referenceLevel);
+					this.options.span = Math.round(metrics.span);
+				}
 				requestAnimationFrame(() => {
 					/*
 					const max = Math.max(...data);
@@ -276,6 +350,10 @@ createApp({
 					prevData = data;
 					*/
 
+					// 表示範囲 [dBm]。トレースとウォーターフォールの色で同じ範囲を使う
+					const span = +this.options.span;
+					const bottom = +this.options.referenceLevel - span;
+					waterfall.setRange(bottom, span);
 					waterfall.renderLine(data);
 
 					ctxFft.fillStyle = "rgba(0, 0, 0, 0.1)";
@@ -291,6 +369,16 @@ createApp({
 					}
 					ctxFft.stroke();
 
+					// 範囲（セグメント）の境目
+					ctxFft.strokeStyle = "rgba(255, 235, 59, 0.4)";
+					ctxFft.beginPath();
+					for (const segment of segments.slice(1)) {
+						const x = canvasFft.width * segment.offset / freqBinCount;
+						ctxFft.moveTo(x, 0);
+						ctxFft.lineTo(x, canvasFft.height);
+					}
+					ctxFft.stroke();
+
 					if (this.options.peakHold) {
 						const now = Date.now();
 						if (now - this.captureStartTime > 1000) {
@@ -306,8 +394,8 @@ createApp({
 						if (this.maxData) {
 							ctxFft.beginPath();
 							ctxFft.moveTo(0, canvasFft.height);
-							for (let i = 0; i < freqBinCount; i++) {
-								const n = (this.maxData[i] + 45) / 42;
+							for (let i = 0; i < columns; i++) {
+								const n = (this.maxData[i] - bottom) / span;
 								ctxFft.lineTo(i, canvasFft.height - canvasFft.height * n);
 							}
 							ctxFft.strokeStyle = "#ffeb3b";
@@ -318,8 +406,8 @@ createApp({
 					ctxFft.save();
 					ctxFft.beginPath();
 					ctxFft.moveTo(0, canvasFft.height);
-					for (let i = 0; i < freqBinCount; i++) {
-						const n = (data[i] + 45) / 42;
+					for (let i = 0; i < columns; i++) {
+						const n = (data[i] - bottom) / span;
 						ctxFft.lineTo(i, canvasFft.height - canvasFft.height * n);
 					}
 					ctxFft.strokeStyle = "#fff";
@@ -329,6 +417,7 @@ createApp({
 			}));
 
 			this.running = true;
+			this.axis = { binCount: freqBinCount, segments };
 			this.captureStartTime = Date.now();
 		},
 
@@ -338,10 +427,18 @@ createApp({
 		},
 
 		labelFor: function (n) {
-			const lowFreq = +this.range.start;
-			const highFreq = +this.range.stop;
-			const bandwidth = highFreq - lowFreq;
-			const freq = bandwidth * n + lowFreq;
+			const { binCount, segments } = this.axis;
+			if (!segments.length) {
+				const lowFreq = +this.range.start;
+				const highFreq = +this.range.stop;
+				const bandwidth = highFreq - lowFreq;
+				const freq = bandwidth * n + lowFreq;
+				return (freq).toFixed(1);
+			}
+			// 列は全セグメントを連結したフレームのビンを等分したもの
+			const bin = Math.min(n * binCount, binCount);
+			const segment = segments.find((s) => bin < s.offset + s.binCount) || segments[segments.length - 1];
+			const freq = segment.lowFreq + (bin - segment.offset) * segment.binWidth;
 			return (freq).toFixed(1);
 		},
 
@@ -375,6 +472,7 @@ createApp({
 		console.log("backend created");
 		await this.backend.init();
 		console.log('backend initialized');
+		await this.backend.setReferenceOffset(+this.options.referenceOffset);
 
 		this.$watch('options.ampEnabled', async (val) => {
 			if (!this.connected) return;
@@ -396,6 +494,15 @@ createApp({
 			await this.backend.setVgaGain(+val);
 		});
 
+		this.$watch('options.frameDetector', async (val) => {
+			if (!this.running) return;
+			await this.backend.setFrameDetector(val);
+		});
+
+		this.$watch('options.referenceOffset', async (val) => {
+			await this.backend.setReferenceOffset(+val);
+		});
+
 		this.$watch('options.peakHold', () => {
 			this.resetPeak();
 		});
@@ -404,10 +511,13 @@ createApp({
 			// 手動で周波数を変更したらプリセット選択をクリア
 			if (this.selectedPreset) {
 				const preset = this.presets.find(p => p.name === this.selectedPreset);
-				if (!preset || preset.start !== this.range.start || preset.stop !== this.range.stop) {
+				if (!preset || preset.start !== this.range.start || preset.stop !== this.range.stop || (preset.extra || "") !== this.range.extra) {
 					this.selectedPreset = null;
 				}
 			}
+			if (!this.running) {
+				this.axis = { binCount: 0, segments: [] };
+			}
 			this.saveSetting();
 		}, { deep: true });
 
@@ -0,0 +1,119 @@
+// hackrf.js の HackRF と同じインターフェースで、wasm の SimulatedHackRF（hackrf-web の SimulatedDevice）を操作する。
+// 受信データは scene から合成するので、USB デバイスなしで worker の掃引から表示までを動かせる
+
+// 1回の転送のあとにイベントループへ戻る間隔 [ms]（受信を止められるように）
+const TRANSFER_INTERVAL_MS = 0;
+
+class HackRFSimulator {
+	// wasm: 初期化済みの hackrf-web モジュール
+	// scene: { noiseDb, carriers: [{ frequencyHz, levelDb }, ...] }（レベルは総ゲイン 0 dB のときの dBFS）
+	constructor(wasm, { noiseDb = -70, carriers = [] } = {}) {
+		this.device = null;
+		this.rxRunning = null;
+		this.sim = new wasm.SimulatedHackRF(noiseDb);
+		for (const { frequencyHz, levelDb } of carriers) {
+			this.sim.add_carrier(frequencyHz, levelDb);
+		}
+	}
+
+	async open() {
+	}
+
+	async readBoardId() {
+		return this.sim.board_id_read();
+	}
+
+	async readVersionString() {
+		return this.sim.version_string_read();
+	}
+
+	async readApiVersion() {
+		const v = this.sim.usb_api_version();
+		return [v >> 8, (v >> 4) & 0x0f, v & 0x0f];
+	}
+
+	async readPartIdSerialNo() {
+		const words = Array.from(this.sim.board_partid_serialno_read());
+		return { partId: words.slice(0, 2), serialNo: words.slice(2) };
+	}
+
+	async boardRevRead() {
+		return this.sim.board_rev_read();
+	}
+
+	async setSampleRateManual(freqHz, divider) {
+		this.sim.set_sample_rate_manual(freqHz, divider);
+	}
+
+	async setBasebandFilterBandwidth(bandwidthHz) {
+		this.sim.set_baseband_filter_bandwidth(bandwidthHz);
+	}
+
+	async setLnaGain(value) {
+		this.sim.set_lna_gain(value);
+	}
+
+	async setVgaGain(value) {
+		this.sim.set_vga_gain(value);
+	}
+
+	async setAmpEnable(enable) {
+		this.sim.set_amp_enable(enable);
+	}
+
+	async setAntennaEnable(enable) {
+		this.sim.set_antenna_enable(enable);
+	}
+
+	async setFreq(freqHz) {
+		this.sim.set_freq(freqHz);
+	}
+
+	async initSweep(ranges, numBytes, stepWidth, offset, style) {
+		this.sim.init_sweep(new Uint16Array(ranges), numBytes, stepWidth, offset, style);
+	}
+
+	async startRx(callback) {
+		if (this.rxRunning) {
+			throw "already started";
+		}
+		this.sim.start_rx();
+		this.rxRunning = this.transfer(callback);
+	}
+
+	async startRxSweep(callback) {
+		if (this.rxRunning) {
+			throw "already started";
+		}
+		this.sim.start_rx_sweep();
+		this.rxRunning = this.transfer(callback);
+	}
+
+	async transfer(callback) {
+		await Promise.resolve();
+		while (this.rxRunning) {
+			callback(this.sim.read_transfer());
+			await new Promise((resolve) => setTimeout(resolve, TRANSFER_INTERVAL_MS));
+		}
+		console.log('rx transfer ended (simulator)');
+	}
+
+	async stopRx() {
+		if (this.rxRunning) {
+			const promise = this.rxRunning;
+			this.rxRunning = null;
+			await promise;
+		}
+		this.sim.stop_rx();
+	}
+
+	async close() {
+		await this.stopRx();
+	}
+
+	async exit() {
+		this.sim.free();
+	}
+}
+
+export { HackRFSimulator };
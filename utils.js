@@ -18,9 +18,10 @@ HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABI
 ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 */
 
-export function convertDecibelToRGB (dB) {
+// bottom [dB] を黒、bottom + span を白とする色
+export function convertDecibelToRGB (dB, bottom, span) {
 	var r = 0, g = 0, b = 0;
-	var p = (dB + 48) / 48;
+	var p = (dB - bottom) / span;
 
 	switch (true) {
 	case p > 5.0/6.0:
@@ -99,6 +100,7 @@ export class WaterfallGL {
 		this.historySize = historySize;
 		this.canvas = canvas;
 		this.data = new Uint8Array(this.bandSize * 4);
+		this.setRange(-48, 48);
 		this.initWebGL();
 	}
 
@@ -248,13 +250,19 @@ export class WaterfallGL {
 		gl.drawArrays(gl.TRIANGLE_STRIP, 0, 4);
 	}
 
+	// 色に割り当てる範囲（下端 [dB] と幅 [dB]）。次の行から使う
+	setRange(bottom, span) {
+		this.bottom = bottom;
+		this.span = span;
+	}
+
 	renderLine(array) {
 		const gl = this.gl;
 		const data = this.data;
 
 		for (let i = 0, len = this.bandSize; i < len; i++) {
 			const n = i * 4;
-			const rgb = convertDecibelToRGB(array[i]);
+			const rgb = convertDecibelToRGB(array[i], this.bottom, this.span);
 
 			data[n + 0] = rgb.r;
 			data[n + 1] = rgb.g;
@@ -293,11 +301,18 @@ export class Waterfall {
 		this.historySize = historySize;
 		this.canvas = canvas;
 		this.data = new Uint8Array(this.bandSize * 4);
+		this.setRange(-48, 48);
 		this.canvas.width  = this.bandSize;
 		this.canvas.height = this.historySize;
 		this.ctx = this.canvas.getContext('2d');
 	}
 
+	// 色に割り当てる範囲（下端 [dB] と幅 [dB]）。次の行から使う
+	setRange(bottom, span) {
+		this.bottom = bottom;
+		this.span = span;
+	}
+
 	renderLine(array) {
 		const { canvas, ctx } = this;
 
@@ -313,7 +328,7 @@ export class Waterfall {
 
 		for (var i = 0, len = canvas.width; i < len; i++) {
 			var n = i * 4;
-			var rgb = convertDecibelToRGB(array[i]);
+			var rgb = convertDecibelToRGB(array[i], this.bottom, this.span);
 
 			data[n + 0] = rgb.r;
 			data[n + 1] = rgb.g;